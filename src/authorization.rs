@@ -0,0 +1,290 @@
+//! Types for restricting routes to groups of users.
+//!
+//! Groups and their members are persisted in the robot's `Store`. Users listed in
+//! `Config::admins` form the reserved `admins` group, whose members can manage the other groups
+//! from chat with the built-in `auth add`, `auth remove`, and `auth list` commands.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::lock::Mutex;
+
+use crate::{
+    callback::CallbackFuture,
+    chat_service::ChatService,
+    command::{Args, FromArgs, Spec, UsageError},
+    message::IncomingMessage,
+    result::Error,
+    route::Route,
+    store::{ScopedStore, Store},
+};
+
+/// The name of the reserved group made up of the users listed in `Config::admins`.
+pub const ADMINS: &str = "admins";
+
+/// Manages group membership for authorizing users.
+#[derive(Clone, Debug)]
+pub struct Authorization<S>
+where
+    S: Store,
+{
+    admins: Arc<Vec<String>>,
+    /// Held while changing groups, so concurrent changes don't overwrite each other.
+    lock: Arc<Mutex<()>>,
+    store: ScopedStore<S>,
+}
+
+impl<S> Authorization<S>
+where
+    S: Store,
+{
+    /// Creates a new `Authorization` persisting groups in the given store.
+    ///
+    /// `admins` are the IDs of the users that make up the `admins` group. Changes to groups made
+    /// through clones of the `Authorization` are applied one at a time.
+    pub fn new(store: &S, admins: Vec<String>) -> Self {
+        Authorization {
+            admins: Arc::new(admins),
+            lock: Arc::new(Mutex::new(())),
+            store: store.scoped("authorization"),
+        }
+    }
+
+    /// Whether or not the user with the given ID is a member of the given group.
+    pub async fn is_member(&self, user_id: &str, group: &str) -> Result<bool, Error> {
+        Ok(self.members(group).await?.iter().any(|id| id == user_id))
+    }
+
+    /// The IDs of the members of the given group.
+    pub async fn members(&self, group: &str) -> Result<Vec<String>, Error> {
+        if group == ADMINS {
+            return Ok(self.admins.to_vec());
+        }
+
+        self.load(&format!("group:{}", group)).await
+    }
+
+    /// The names of all groups, including `admins`.
+    pub async fn groups(&self) -> Result<Vec<String>, Error> {
+        let mut groups = vec![ADMINS.to_owned()];
+
+        groups.extend(self.load("groups").await?);
+
+        Ok(groups)
+    }
+
+    /// Adds the user with the given ID to the given group.
+    ///
+    /// Returns `false` if the user was already a member.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the group is `admins`, which can only be changed in `Config`.
+    pub async fn add(&self, user_id: &str, group: &str) -> Result<bool, Error> {
+        Self::check_mutable(group)?;
+
+        let _guard = self.lock.lock().await;
+
+        let mut members = self.members(group).await?;
+
+        if members.iter().any(|id| id == user_id) {
+            return Ok(false);
+        }
+
+        members.push(user_id.to_owned());
+        self.save(&format!("group:{}", group), &members).await?;
+
+        let mut groups = self.load("groups").await?;
+
+        if !groups.iter().any(|name| name == group) {
+            groups.push(group.to_owned());
+            groups.sort();
+            self.save("groups", &groups).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Removes the user with the given ID from the given group.
+    ///
+    /// Returns `false` if the user was not a member.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the group is `admins`, which can only be changed in `Config`.
+    pub async fn remove(&self, user_id: &str, group: &str) -> Result<bool, Error> {
+        Self::check_mutable(group)?;
+
+        let _guard = self.lock.lock().await;
+
+        let mut members = self.members(group).await?;
+        let count = members.len();
+
+        members.retain(|id| id != user_id);
+
+        if members.len() == count {
+            return Ok(false);
+        }
+
+        self.save(&format!("group:{}", group), &members).await?;
+
+        if members.is_empty() {
+            let mut groups = self.load("groups").await?;
+
+            groups.retain(|name| name != group);
+            self.save("groups", &groups).await?;
+        }
+
+        Ok(true)
+    }
+
+    fn check_mutable(group: &str) -> Result<(), Error> {
+        if group == ADMINS {
+            Err(Error::custom(format!(
+                "The \"{}\" group can only be changed in the robot's configuration.",
+                ADMINS
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn load(&self, key: &str) -> Result<Vec<String>, Error> {
+        Ok(self.store.get_json(key).await?.unwrap_or_default())
+    }
+
+    async fn save(&self, key: &str, values: &[String]) -> Result<(), Error> {
+        self.store.set_json(key, values).await
+    }
+}
+
+/// Creates the routes for the built-in commands that manage groups, restricted to admins.
+pub(crate) fn routes<C, S>(authorization: &Authorization<S>) -> Vec<Route<C, S>>
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    let add = authorization.clone();
+    let remove = authorization.clone();
+    let list = authorization.clone();
+
+    vec![
+        Route::command("auth add", move |chat, message, args: Membership, _| {
+            let authorization = add.clone();
+
+            reply(chat, message, async move {
+                if authorization.add(&args.user, &args.group).await? {
+                    Ok(format!(
+                        "Added {} to the \"{}\" group.",
+                        args.user, args.group
+                    ))
+                } else {
+                    Ok(format!(
+                        "{} is already in the \"{}\" group.",
+                        args.user, args.group
+                    ))
+                }
+            })
+        })
+        .with_help("Adds a user to a group."),
+        Route::command("auth remove", move |chat, message, args: Membership, _| {
+            let authorization = remove.clone();
+
+            reply(chat, message, async move {
+                if authorization.remove(&args.user, &args.group).await? {
+                    Ok(format!(
+                        "Removed {} from the \"{}\" group.",
+                        args.user, args.group
+                    ))
+                } else {
+                    Ok(format!(
+                        "{} isn't in the \"{}\" group.",
+                        args.user, args.group
+                    ))
+                }
+            })
+        })
+        .with_help("Removes a user from a group."),
+        Route::command("auth list", move |chat, message, args: Listing, _| {
+            let authorization = list.clone();
+
+            reply(chat, message, async move {
+                let groups = match args.group {
+                    Some(group) => vec![group],
+                    None => authorization.groups().await?,
+                };
+                let mut lines = Vec::new();
+
+                for group in groups {
+                    let members = authorization.members(&group).await?;
+
+                    if members.is_empty() {
+                        lines.push(format!("{}: (no members)", group));
+                    } else {
+                        lines.push(format!("{}: {}", group, members.join(", ")));
+                    }
+                }
+
+                Ok(lines.join("\n"))
+            })
+        })
+        .with_help("Lists the members of a group, or of all groups."),
+    ]
+    .into_iter()
+    .map(|route| route.with_required_group(ADMINS))
+    .collect()
+}
+
+/// Replies to the message with the output of a command, or with the error it produced.
+fn reply<C, F>(chat: Arc<C>, message: &IncomingMessage, output: F) -> CallbackFuture
+where
+    C: ChatService + 'static,
+    F: Future<Output = Result<String, Error>> + 'static,
+{
+    let message = message.clone();
+
+    Box::pin(async move {
+        let body = match output.await {
+            Ok(body) => body,
+            Err(error) => error.to_string(),
+        };
+
+        chat.send_message(message.reply(body)).await
+    })
+}
+
+/// The arguments to the `auth add` and `auth remove` commands.
+struct Membership {
+    group: String,
+    user: String,
+}
+
+impl FromArgs for Membership {
+    fn spec(spec: Spec) -> Spec {
+        spec.positional("user").positional("group")
+    }
+
+    fn from_args(args: Args) -> Result<Self, UsageError> {
+        Ok(Membership {
+            group: args.required("group")?,
+            user: args.required("user")?,
+        })
+    }
+}
+
+/// The arguments to the `auth list` command.
+struct Listing {
+    group: Option<String>,
+}
+
+impl FromArgs for Listing {
+    fn spec(spec: Spec) -> Spec {
+        spec.optional("group")
+    }
+
+    fn from_args(args: Args) -> Result<Self, UsageError> {
+        Ok(Listing {
+            group: args.parse("group")?,
+        })
+    }
+}
@@ -22,5 +22,21 @@ where
     }
 }
 
+/// A callback that runs independently of any incoming message, such as a shutdown hook.
+pub trait Task<C, S> {
+    /// Invokes the task.
+    fn call(&self, chat: Arc<C>, store: S) -> CallbackFuture;
+}
+
+impl<F, C, S> Task<C, S> for F
+where
+    F: Fn(Arc<C>, S) -> CallbackFuture,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, store: S) -> CallbackFuture {
+        self(chat, store)
+    }
+}
+
 /// The type returned by callbacks.
 pub type CallbackFuture = Pin<Box<dyn Future<Output = Result<(), Error>>>>;
@@ -0,0 +1,88 @@
+//! Types for cooperatively cancelling work when the robot shuts down.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// A signal that the robot is shutting down and that in-flight work should finish up.
+///
+/// Callbacks can check for cancellation with `IncomingMessage::is_cancelled` or wait for it with
+/// `IncomingMessage::cancelled`, for example to stop a long-running loop early. All clones of a
+/// `Cancellation` share the same state.
+#[derive(Clone, Debug)]
+pub struct Cancellation {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Cancellation {
+    /// Creates a new `Cancellation` that has not been cancelled.
+    pub(crate) fn new() -> Self {
+        Cancellation {
+            inner: Arc::new(Inner::default()),
+        }
+    }
+
+    /// Signals cancellation, waking any tasks waiting for it.
+    pub(crate) fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        if let Ok(mut wakers) = self.inner.wakers.lock() {
+            for waker in wakers.drain(..) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Whether or not cancellation has been signalled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future that completes once cancellation has been signalled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            cancellation: self.clone(),
+        }
+    }
+}
+
+/// A future that completes once cancellation has been signalled.
+///
+/// Created by `Cancellation::cancelled`.
+#[derive(Debug)]
+pub struct Cancelled {
+    cancellation: Cancellation,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = &self.cancellation.inner;
+
+        if inner.cancelled.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+
+        if let Ok(mut wakers) = inner.wakers.lock() {
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+        }
+
+        // Check again in case cancellation was signalled while registering the waker.
+        if inner.cancelled.load(Ordering::SeqCst) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -1,17 +1,22 @@
 //! Types for connecting Rustin to a chat service.
 
+use std::collections::BTreeSet;
 use std::future::Future;
 use std::pin::Pin;
 
-use futures::Stream;
+use futures::{future::ok, stream::empty, Stream};
 
-use crate::message::{IncomingMessage, OutgoingMessage};
+use crate::event::Events;
+use crate::format::{PlainText, Renderer};
+use crate::message::{IncomingMessage, MessageId, OutgoingMessage, Target};
 use crate::result::{Error, Success};
 use crate::room::Room;
 use crate::user::User;
 
+pub use self::chat::Chat;
 pub use self::shell::Shell;
 
+mod chat;
 mod shell;
 
 /// A type that handles the implementation details of the Robot API for a particular chat service.
@@ -20,18 +25,87 @@ mod shell;
 /// single room only.
 pub trait ChatService {
     /// Sends a message to a chat room or user.
+    ///
+    /// Messages sent by the robot's callbacks and tasks are at most `max_message_length`
+    /// characters long, as the robot sends longer ones in parts.
     fn send_message(&self, message: OutgoingMessage) -> Success;
 
     /// Connects to the chat service and listens for incoming messages.
     fn incoming(&self, alias: Option<String>) -> Incoming;
 
+    /// The features the chat service supports beyond sending and receiving messages.
+    ///
+    /// Plugins and the robot itself use this to adapt their behavior at runtime. The default
+    /// implementation returns an empty set.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::new()
+    }
+
+    /// The maximum length of a message, in characters, if the chat service limits it.
+    ///
+    /// The robot splits longer messages with `OutgoingMessage::split` before sending them, and
+    /// callbacks with a lot to say can use this to decide how to present it. The default
+    /// implementation returns `None`.
+    fn max_message_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// The renderer the chat service uses for formatted messages, which the robot uses to measure
+    /// messages against `max_message_length`.
+    ///
+    /// The default implementation returns `PlainText`.
+    fn renderer(&self) -> &dyn Renderer {
+        &PlainText
+    }
+
+    /// Returns a stream of events from the chat service other than messages, such as users
+    /// joining rooms.
+    ///
+    /// The robot calls this once, before calling `incoming`. The default implementation returns a
+    /// stream with no events.
+    fn events(&self) -> Events {
+        Box::pin(empty())
+    }
+
     /// Returns a `User` representing the robot.
     ///
     /// May be absent if not applicable or if called before the robot has connected to the chat
     /// service and retrieved data about itself.
     fn user(&self) -> Pin<Box<dyn Future<Output = Result<User, Error>>>>;
+
+    /// Disconnects from the chat service and releases any resources held by the adapter.
+    ///
+    /// Called by the robot when it shuts down. The default implementation does nothing.
+    fn shutdown(&self) -> Success {
+        Box::pin(ok(()))
+    }
+}
+
+/// A feature that a chat service may support beyond sending and receiving messages.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Capability {
+    /// Uploading files attached to outgoing messages.
+    ///
+    /// Chat services without this capability should send any attached files as notices instead,
+    /// such as with `OutgoingMessage::without_attachments`.
+    Attachments,
+    /// Sending messages directly to a user.
+    DirectMessages,
+    /// Editing and deleting messages that have been sent, through `EditingChatService`.
+    Edits,
+    /// Being in more than one room at a time.
+    MultipleRooms,
+    /// Reacting to messages with emoji, through `ReactingChatService`.
+    Reactions,
+    /// Organizing messages into threads.
+    Threads,
+    /// Showing that the robot is typing, through `PresenceChatService`.
+    Typing,
 }
 
+/// The set of features a chat service supports, as returned by `ChatService::capabilities`.
+pub type Capabilities = BTreeSet<Capability>;
+
 /// A `ChatService` that supports joining and parting from multiple rooms.
 pub trait MultiRoomChatService: ChatService {
     /// Makes Rustin join a chat room.
@@ -41,5 +115,70 @@ pub trait MultiRoomChatService: ChatService {
     fn part(&self, room: &Room) -> Success;
 }
 
+/// A `ChatService` that can edit and delete messages after sending them.
+///
+/// Chat services implementing this trait should include `Capability::Edits` in their
+/// capabilities.
+pub trait EditingChatService: ChatService {
+    /// Sends a message to a chat room or user like `send_message`, returning the id the chat
+    /// service gave the message so it can be edited or deleted later.
+    fn send_message_with_id(&self, message: OutgoingMessage) -> Sent;
+
+    /// Replaces the body of a message the robot sent with that of `message`.
+    ///
+    /// The target of `message` should be the same as that of the message being edited.
+    fn edit_message(&self, id: &MessageId, message: OutgoingMessage) -> Success;
+
+    /// Deletes a message the robot sent.
+    fn delete_message(&self, id: &MessageId) -> Success;
+}
+
+/// The type returned by `EditingChatService::send_message_with_id`.
+pub type Sent = Pin<Box<dyn Future<Output = Result<MessageId, Error>>>>;
+
+/// A `ChatService` that can add emoji reactions to messages.
+///
+/// Chat services implementing this trait should include `Capability::Reactions` in their
+/// capabilities. Emoji are identified by their names without colons, such as `+1` or `eyes`.
+pub trait ReactingChatService: ChatService {
+    /// Adds a reaction with the given emoji to a message.
+    fn add_reaction(&self, message: &MessageId, emoji: &str) -> Success;
+
+    /// Removes the robot's reaction with the given emoji from a message.
+    fn remove_reaction(&self, message: &MessageId, emoji: &str) -> Success;
+}
+
+/// A `ChatService` that can show that the robot is typing and set the robot's presence.
+///
+/// Chat services implementing this trait should include `Capability::Typing` in their
+/// capabilities.
+pub trait PresenceChatService: ChatService {
+    /// Shows that the robot is typing to a room, user, or thread.
+    ///
+    /// The indicator should last until `stop_typing` is called or the robot sends a message to the
+    /// target. Chat services whose typing indicators expire on their own should renew them until
+    /// then.
+    fn start_typing(&self, target: &Target) -> Success;
+
+    /// Stops showing that the robot is typing to a room, user, or thread.
+    fn stop_typing(&self, target: &Target) -> Success;
+
+    /// Sets whether the robot appears available or away.
+    ///
+    /// The default implementation does nothing, for chat services without presence.
+    fn set_presence(&self, _presence: Presence) -> Success {
+        Box::pin(ok(()))
+    }
+}
+
+/// Whether the robot appears available to users.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Presence {
+    /// The robot is online and responding.
+    Available,
+    /// The robot is online but not responding, such as during maintenance.
+    Away,
+}
+
 /// An asynchronous stream of incoming messages.
 pub type Incoming = Pin<Box<dyn Stream<Item = Result<IncomingMessage, Error>>>>;
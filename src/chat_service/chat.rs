@@ -0,0 +1,200 @@
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use super::{
+    Capabilities,
+    ChatService,
+    EditingChatService,
+    Incoming,
+    MultiRoomChatService,
+    Presence,
+    PresenceChatService,
+    ReactingChatService,
+    Sent,
+};
+use crate::{
+    event::Events,
+    format::Renderer,
+    message::{MessageId, OutgoingMessage, Target},
+    result::{Error, Success},
+    room::Room,
+    user::User,
+};
+
+/// A chat service as the robot hands it to callbacks, scheduled tasks, and other handlers.
+///
+/// Messages sent through it are split with `OutgoingMessage::split` to fit the chat service's
+/// `ChatService::max_message_length`, and the parts are sent in order. Everything else is passed
+/// through to the chat service, which can also be reached directly by dereferencing.
+#[derive(Debug)]
+pub struct Chat<C> {
+    inner: Arc<C>,
+}
+
+impl<C> Chat<C>
+where
+    C: ChatService,
+{
+    /// Wraps a chat service.
+    pub fn new(chat_service: C) -> Self {
+        Chat {
+            inner: Arc::new(chat_service),
+        }
+    }
+
+    /// Splits a message into the parts to send, if the chat service limits message length.
+    fn split(&self, message: OutgoingMessage) -> Vec<OutgoingMessage> {
+        match self.inner.max_message_length() {
+            Some(max_length) => message.split(self.inner.renderer(), max_length),
+            None => vec![message],
+        }
+    }
+}
+
+impl<C> Clone for Chat<C> {
+    fn clone(&self) -> Self {
+        Chat {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<C> Deref for Chat<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C> ChatService for Chat<C>
+where
+    C: ChatService + 'static,
+{
+    fn send_message(&self, message: OutgoingMessage) -> Success {
+        let mut parts = self.split(message);
+
+        if parts.len() == 1 {
+            return self.inner.send_message(parts.remove(0));
+        }
+
+        let inner = self.inner.clone();
+
+        Box::pin(async move {
+            for part in parts {
+                inner.send_message(part).await?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn incoming(&self, alias: Option<String>) -> Incoming {
+        self.inner.incoming(alias)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    fn max_message_length(&self) -> Option<usize> {
+        self.inner.max_message_length()
+    }
+
+    fn renderer(&self) -> &dyn Renderer {
+        self.inner.renderer()
+    }
+
+    fn events(&self) -> Events {
+        self.inner.events()
+    }
+
+    fn user(&self) -> Pin<Box<dyn Future<Output = Result<User, Error>>>> {
+        self.inner.user()
+    }
+
+    fn shutdown(&self) -> Success {
+        self.inner.shutdown()
+    }
+}
+
+impl<C> MultiRoomChatService for Chat<C>
+where
+    C: MultiRoomChatService + 'static,
+{
+    fn join(&self, room: &Room) -> Success {
+        self.inner.join(room)
+    }
+
+    fn part(&self, room: &Room) -> Success {
+        self.inner.part(room)
+    }
+}
+
+/// Messages too long to send at once are sent in parts, and the id of the last part is returned.
+/// Edits are passed through as is, since one message can't be edited into several.
+impl<C> EditingChatService for Chat<C>
+where
+    C: EditingChatService + 'static,
+{
+    fn send_message_with_id(&self, message: OutgoingMessage) -> Sent {
+        let mut parts = self.split(message);
+        let last = parts
+            .pop()
+            .expect("splitting a message produces at least one part");
+
+        if parts.is_empty() {
+            return self.inner.send_message_with_id(last);
+        }
+
+        let inner = self.inner.clone();
+
+        Box::pin(async move {
+            for part in parts {
+                inner.send_message(part).await?;
+            }
+
+            inner.send_message_with_id(last).await
+        })
+    }
+
+    fn edit_message(&self, id: &MessageId, message: OutgoingMessage) -> Success {
+        self.inner.edit_message(id, message)
+    }
+
+    fn delete_message(&self, id: &MessageId) -> Success {
+        self.inner.delete_message(id)
+    }
+}
+
+impl<C> ReactingChatService for Chat<C>
+where
+    C: ReactingChatService + 'static,
+{
+    fn add_reaction(&self, message: &MessageId, emoji: &str) -> Success {
+        self.inner.add_reaction(message, emoji)
+    }
+
+    fn remove_reaction(&self, message: &MessageId, emoji: &str) -> Success {
+        self.inner.remove_reaction(message, emoji)
+    }
+}
+
+impl<C> PresenceChatService for Chat<C>
+where
+    C: PresenceChatService + 'static,
+{
+    fn start_typing(&self, target: &Target) -> Success {
+        self.inner.start_typing(target)
+    }
+
+    fn stop_typing(&self, target: &Target) -> Success {
+        self.inner.stop_typing(target)
+    }
+
+    fn set_presence(&self, presence: Presence) -> Success {
+        self.inner.set_presence(presence)
+    }
+}
@@ -1,29 +1,50 @@
+use std::env;
+use std::fs;
 use std::future::Future;
 use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use futures::{channel::mpsc::channel, executor::block_on, future::ok};
+use futures::{
+    channel::mpsc::{channel, unbounded, UnboundedSender},
+    executor::block_on,
+    future::{err, ok},
+};
 use regex::{escape, Regex};
 
-use super::{ChatService, Incoming};
+use super::{Capabilities, Capability, ChatService, Incoming};
 use crate::{
-    message::{IncomingMessage, OutgoingMessage, Source},
+    entity,
+    event::{Event, Events},
+    format::PlainText,
+    message::{Attachment, IncomingMessage, OutgoingMessage, Source},
     result::{Error, Success},
     user::User,
 };
 
 /// An adapter that runs in your shell.
+///
+/// The shell emits `Event::Connected` when the session starts and `Event::Disconnected` when it
+/// ends. Mentions, room references, and links in messages are found with `entity::parse`. Files
+/// attached to outgoing messages are written to a `rustin` directory inside the system's temporary
+/// directory, and their paths are printed after the message.
 #[derive(Clone, Debug)]
 pub struct Shell {
+    events: Arc<Mutex<Option<EventSender>>>,
     user: User,
 }
 
+/// The sending half of the channel returned by `Shell::events`.
+type EventSender = UnboundedSender<Result<Event, Error>>;
+
 impl Shell {
     /// Creates a new `Shell` with the given name for the robot.
     pub fn new(name: &str) -> Self {
         Shell {
+            events: Arc::new(Mutex::new(None)),
             user: User::new("1", Some(name), None),
         }
     }
@@ -40,26 +61,46 @@ impl Shell {
 
 impl Default for Shell {
     fn default() -> Self {
-        Shell {
-            user: User::new("1", Some("Rustin"), None),
-        }
+        Shell::new("Rustin")
     }
 }
 
 impl ChatService for Shell {
     fn send_message(&self, message: OutgoingMessage) -> Success {
-        println!("{}", message);
+        println!("{}", message.render(&PlainText));
+
+        for attachment in message.attachments() {
+            match save_attachment(&env::temp_dir().join("rustin"), attachment) {
+                Ok(path) => println!("Attachment: {}", path.display()),
+                Err(error) => return Box::pin(err(Error::custom(error.to_string()))),
+            }
+        }
 
         Box::pin(ok(()))
     }
 
+    fn capabilities(&self) -> Capabilities {
+        vec![Capability::Attachments, Capability::DirectMessages]
+            .into_iter()
+            .collect()
+    }
+
     fn incoming(&self, alias: Option<String>) -> Incoming {
         let (mut tx, rx) = channel(0);
         let mention_regex = self.mention_regex(alias);
         let robot = block_on(self.user()).expect("accessing robot user");
         let prompt = format!("{} > ", robot.username().expect("accessing username"));
+        let events = self.events.lock().expect("accessing event sender").clone();
 
         thread::spawn(move || {
+            let emit = |event| {
+                if let Some(ref events) = events {
+                    let _ = events.unbounded_send(Ok(event));
+                }
+            };
+
+            emit(Event::Connected);
+
             let input = io::stdin();
             let mut output = io::stdout();
             let duration = Duration::from_millis(10);
@@ -72,7 +113,7 @@ impl ChatService for Shell {
             for line in input.lock().lines() {
                 match line {
                     Ok(body) => {
-                        if body.len() == 0 {
+                        if body.is_empty() {
                             print!("{}", prompt);
                             output.flush().unwrap();
 
@@ -90,7 +131,9 @@ impl ChatService for Shell {
 
                         let user = User::new("1", Some("Shell User"), None);
                         let source = Source::User(user);
-                        let message = IncomingMessage::new(source, body, content_offset);
+                        let entities = entity::parse(&body);
+                        let message = IncomingMessage::new(source, body, content_offset)
+                            .with_entities(entities);
 
                         match tx.try_send(Ok(message)) {
                             Ok(_) => {
@@ -111,12 +154,63 @@ impl ChatService for Shell {
             if dirty_exit {
                 println!();
             }
+
+            emit(Event::Disconnected);
         });
 
         Box::pin(rx)
     }
 
+    fn events(&self) -> Events {
+        let (tx, rx) = unbounded();
+
+        *self.events.lock().expect("accessing event sender") = Some(tx);
+
+        Box::pin(rx)
+    }
+
     fn user(&self) -> Pin<Box<dyn Future<Output = Result<User, Error>>>> {
         Box::pin(ok(self.user.clone()))
     }
 }
+
+/// Writes an attachment to a uniquely named file in the given directory, returning its path.
+fn save_attachment(directory: &Path, attachment: &Attachment) -> io::Result<PathBuf> {
+    // Only use the final component of the name so the file can't escape the directory.
+    let filename = Path::new(attachment.filename())
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("attachment");
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+    let path = directory.join(format!("{}-{}", nanos, filename));
+
+    fs::create_dir_all(directory)?;
+    fs::write(&path, attachment.data())?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::save_attachment;
+    use crate::message::Attachment;
+
+    #[test]
+    fn saves_attachments_inside_the_directory() {
+        let directory = env::temp_dir().join("rustin-shell-test");
+        let attachment = Attachment::new("../../report.csv", "text/csv", b"a,b\n1,2\n".to_vec());
+        let path = save_attachment(&directory, &attachment).unwrap();
+
+        assert_eq!(path.parent(), Some(directory.as_path()));
+        assert!(path.to_str().unwrap().ends_with("-report.csv"));
+        assert_eq!(fs::read(&path).unwrap(), attachment.data());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,505 @@
+//! Types for command-style routes that parse their arguments like a command line program.
+//!
+//! A command route is created with `Route::command`. When a directed message begins with the
+//! command's name, the rest of the message is split into words using shell-like quoting and parsed
+//! according to a `Spec`. If the arguments are invalid, the robot replies with the error and the
+//! command's usage instead of invoking the callback.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{
+    callback::{Callback, CallbackFuture},
+    chat_service::ChatService,
+    message::IncomingMessage,
+    result::Error,
+    store::Store,
+};
+
+/// A type that can be created from a command's parsed arguments.
+///
+/// # Examples
+///
+/// ```
+/// use rustin::command::{Args, FromArgs, Spec, UsageError};
+///
+/// struct Deploy {
+///     app: String,
+///     env: String,
+///     force: bool,
+/// }
+///
+/// impl FromArgs for Deploy {
+///     fn spec(spec: Spec) -> Spec {
+///         spec.positional("app").option("env").flag("force")
+///     }
+///
+///     fn from_args(args: Args) -> Result<Self, UsageError> {
+///         Ok(Deploy {
+///             app: args.required("app")?,
+///             env: args.parse("env")?.unwrap_or_else(|| "staging".to_owned()),
+///             force: args.flag("force"),
+///         })
+///     }
+/// }
+/// ```
+pub trait FromArgs: Sized {
+    /// Declares the arguments accepted by the command, adding them to the given `Spec`.
+    fn spec(spec: Spec) -> Spec;
+
+    /// Creates a value from the parsed arguments.
+    fn from_args(args: Args) -> Result<Self, UsageError>;
+}
+
+/// Accepts any number of positional arguments, available via `Args::rest`.
+impl FromArgs for Args {
+    fn spec(spec: Spec) -> Spec {
+        spec.rest("args")
+    }
+
+    fn from_args(args: Args) -> Result<Self, UsageError> {
+        Ok(args)
+    }
+}
+
+/// A description of the arguments accepted by a command.
+#[derive(Clone, Debug)]
+pub struct Spec {
+    name: String,
+    parameters: Vec<Parameter>,
+}
+
+impl Spec {
+    /// Creates a new `Spec` for the command with the given name and no arguments.
+    pub fn new<N>(name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        Spec {
+            name: name.into(),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Adds a required positional argument.
+    pub fn positional(self, name: &str) -> Self {
+        self.parameter(name, Kind::Positional)
+    }
+
+    /// Adds an optional positional argument.
+    ///
+    /// Optional positional arguments should be declared after all required ones.
+    pub fn optional(self, name: &str) -> Self {
+        self.parameter(name, Kind::Optional)
+    }
+
+    /// Collects any remaining positional arguments.
+    pub fn rest(self, name: &str) -> Self {
+        self.parameter(name, Kind::Rest)
+    }
+
+    /// Adds a boolean flag, given as `--name`.
+    pub fn flag(self, name: &str) -> Self {
+        self.parameter(name, Kind::Flag)
+    }
+
+    /// Adds an option that takes a value, given as `--name value` or `--name=value`.
+    pub fn option(self, name: &str) -> Self {
+        self.parameter(name, Kind::Option)
+    }
+
+    /// The name of the command.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A one line summary of how to invoke the command, such as `deploy <app> [--force]`.
+    pub fn usage(&self) -> String {
+        let mut usage = self.name.clone();
+
+        for parameter in &self.parameters {
+            let name = &parameter.name;
+
+            let text = match parameter.kind {
+                Kind::Positional => format!("<{}>", name),
+                Kind::Optional => format!("[<{}>]", name),
+                Kind::Rest => format!("[<{}>...]", name),
+                Kind::Flag => format!("[--{}]", name),
+                Kind::Option => format!("[--{} <{}>]", name, name),
+            };
+
+            usage.push(' ');
+            usage.push_str(&text);
+        }
+
+        usage
+    }
+
+    /// Splits the given input into words and parses them as arguments to the command.
+    ///
+    /// The input should not include the name of the command.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input has an unterminated quote or if the words don't match the
+    /// arguments declared by the spec.
+    pub fn parse(&self, input: &str) -> Result<Args, UsageError> {
+        let mut args = Args::default();
+        let mut positionals = Vec::new();
+        let mut words = tokenize(input)?.into_iter();
+        let mut options_ended = false;
+
+        while let Some(word) = words.next() {
+            if options_ended || !word.starts_with("--") {
+                positionals.push(word);
+                continue;
+            }
+
+            if word == "--" {
+                options_ended = true;
+                continue;
+            }
+
+            let (name, inline_value) = match word[2..].find('=') {
+                Some(index) => (&word[2..index + 2], Some(word[index + 3..].to_owned())),
+                None => (&word[2..], None),
+            };
+
+            match self.find(name).map(|parameter| &parameter.kind) {
+                Some(Kind::Flag) => {
+                    if inline_value.is_some() {
+                        return Err(UsageError::new(format!("--{} does not take a value", name)));
+                    }
+
+                    args.flags.insert(name.to_owned());
+                }
+                Some(Kind::Option) => {
+                    let value = inline_value
+                        .or_else(|| words.next())
+                        .ok_or_else(|| UsageError::new(format!("--{} requires a value", name)))?;
+
+                    args.values.insert(name.to_owned(), value);
+                }
+                _ => return Err(UsageError::new(format!("unknown option --{}", name))),
+            }
+        }
+
+        let mut positionals = positionals.into_iter();
+
+        for parameter in &self.parameters {
+            match parameter.kind {
+                Kind::Positional => match positionals.next() {
+                    Some(value) => {
+                        args.values.insert(parameter.name.clone(), value);
+                    }
+                    None => {
+                        return Err(UsageError::new(format!(
+                            "missing argument <{}>",
+                            parameter.name
+                        )))
+                    }
+                },
+                Kind::Optional => {
+                    if let Some(value) = positionals.next() {
+                        args.values.insert(parameter.name.clone(), value);
+                    }
+                }
+                Kind::Rest => args.rest.extend(positionals.by_ref()),
+                Kind::Flag | Kind::Option => {}
+            }
+        }
+
+        match positionals.next() {
+            Some(extra) => Err(UsageError::new(format!(
+                "unexpected argument \"{}\"",
+                extra
+            ))),
+            None => Ok(args),
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&Parameter> {
+        self.parameters
+            .iter()
+            .find(|parameter| parameter.name == name)
+    }
+
+    fn parameter(mut self, name: &str, kind: Kind) -> Self {
+        self.parameters.push(Parameter {
+            kind,
+            name: name.to_owned(),
+        });
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Parameter {
+    kind: Kind,
+    name: String,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Flag,
+    Option,
+    Optional,
+    Positional,
+    Rest,
+}
+
+/// The arguments given to a command, parsed according to its `Spec`.
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    flags: HashSet<String>,
+    rest: Vec<String>,
+    values: HashMap<String, String>,
+}
+
+impl Args {
+    /// Whether or not the given flag was present.
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    /// The value of the given positional argument or option, if present.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Parses the value of the given positional argument or option, if present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value can't be parsed as a `T`.
+    pub fn parse<T>(&self, name: &str) -> Result<Option<T>, UsageError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.value(name)
+            .map(|value| {
+                value.parse().map_err(|error| {
+                    UsageError::new(format!("invalid value for {}: {}", name, error))
+                })
+            })
+            .transpose()
+    }
+
+    /// Parses the value of the given positional argument or option, which must be present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is absent or can't be parsed as a `T`.
+    pub fn required<T>(&self, name: &str) -> Result<T, UsageError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.parse(name)?
+            .ok_or_else(|| UsageError::new(format!("missing argument <{}>", name)))
+    }
+
+    /// Any positional arguments collected by `Spec::rest`.
+    pub fn rest(&self) -> &[String] {
+        &self.rest
+    }
+}
+
+/// An error explaining why a command's arguments are invalid.
+#[derive(Clone, Debug)]
+pub struct UsageError {
+    message: String,
+}
+
+impl UsageError {
+    /// Creates a new `UsageError` from a message.
+    pub fn new<M>(message: M) -> Self
+    where
+        M: Into<String>,
+    {
+        UsageError {
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl StdError for UsageError {}
+
+impl From<UsageError> for Error {
+    fn from(error: UsageError) -> Self {
+        Error::custom(error.message)
+    }
+}
+
+/// Splits input into words, honoring single quotes, double quotes, and backslash escapes.
+///
+/// # Errors
+///
+/// Returns an error if a quote is never closed.
+pub fn tokenize(input: &str) -> Result<Vec<String>, UsageError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(UsageError::new("unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(UsageError::new("unterminated double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(UsageError::new("unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => {
+                let word = word.get_or_insert_with(String::new);
+
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    words.extend(word);
+
+    Ok(words)
+}
+
+/// The callback for a command route, which parses arguments before invoking the user's callback.
+pub(crate) struct Command<A, F> {
+    callback: F,
+    spec: Spec,
+    args: PhantomData<fn() -> A>,
+}
+
+impl<A, F> Command<A, F>
+where
+    A: FromArgs,
+{
+    pub(crate) fn new(name: &str, callback: F) -> Self {
+        Command {
+            callback,
+            spec: A::spec(Spec::new(name)),
+            args: PhantomData,
+        }
+    }
+
+    pub(crate) fn spec(&self) -> &Spec {
+        &self.spec
+    }
+}
+
+impl<A, F, C, S> Callback<C, S> for Command<A, F>
+where
+    A: FromArgs,
+    F: Fn(Arc<C>, &IncomingMessage, A, S) -> CallbackFuture,
+    C: ChatService,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, message: &IncomingMessage, store: S) -> CallbackFuture {
+        let input = message
+            .body()
+            .trim_start()
+            .get(self.spec.name().len()..)
+            .unwrap_or("");
+
+        if tokenize(input).is_ok_and(|words| words.iter().any(|word| word == "--help")) {
+            return chat.send_message(message.reply(format!("Usage: {}", self.spec.usage())));
+        }
+
+        match self.spec.parse(input).and_then(A::from_args) {
+            Ok(args) => (self.callback)(chat, message, args, store),
+            Err(error) => {
+                chat.send_message(message.reply(format!("{}\nUsage: {}", error, self.spec.usage())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Spec};
+
+    #[test]
+    fn tokenize_with_quotes_and_escapes() {
+        let words = tokenize(r#"app "two words" 'it''s' a\ b "say \"hi\"" """#).unwrap();
+
+        assert_eq!(
+            words,
+            vec!["app", "two words", "its", "a b", "say \"hi\"", ""]
+        );
+        assert!(tokenize("'open").is_err());
+    }
+
+    #[test]
+    fn parse_positionals_flags_and_options() {
+        let spec = Spec::new("deploy")
+            .positional("app")
+            .optional("region")
+            .option("env")
+            .flag("force");
+
+        assert_eq!(
+            spec.usage(),
+            "deploy <app> [<region>] [--env <env>] [--force]"
+        );
+
+        let args = spec.parse("web --env=prod --force").unwrap();
+        assert_eq!(args.value("app"), Some("web"));
+        assert_eq!(args.value("region"), None);
+        assert_eq!(args.value("env"), Some("prod"));
+        assert!(args.flag("force"));
+
+        let error = spec.parse("--env staging web us -- --force").unwrap_err();
+        assert_eq!(error.to_string(), "unexpected argument \"--force\"");
+
+        assert_eq!(
+            spec.parse("--force").unwrap_err().to_string(),
+            "missing argument <app>"
+        );
+        assert_eq!(
+            spec.parse("web --env").unwrap_err().to_string(),
+            "--env requires a value"
+        );
+        assert_eq!(
+            spec.parse("web --verbose").unwrap_err().to_string(),
+            "unknown option --verbose"
+        );
+    }
+}
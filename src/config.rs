@@ -1,8 +1,15 @@
 //! Configuration data for Rustin.
 
+use std::net::SocketAddr;
+use std::time::Duration;
+
 /// Configuration data for a `Robot`.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Config {
+    /// The IDs of the users that may manage authorization groups from chat.
+    ///
+    /// These users make up the reserved `admins` group, which can't be changed from chat.
+    pub admins: Vec<String>,
     /// An alias for the robot.
     ///
     /// The robot normally determines that a message is directed to it by looking for its username
@@ -12,10 +19,22 @@ pub struct Config {
     /// This is commonly set to a single character like `/` or `!` as a short way of sending
     /// commands to the robot in chat.
     pub alias: Option<String>,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Config { alias: None }
-    }
+    /// The maximum amount of time a route's callback may run before it fails with a timeout
+    /// error.
+    ///
+    /// Routes can override this with `Route::with_timeout`. When neither is set, callbacks may run
+    /// indefinitely.
+    pub timeout: Option<Duration>,
+    /// The maximum amount of time to wait for in-flight callbacks to finish when the robot shuts
+    /// down.
+    ///
+    /// Callbacks that are still running when this elapses are dropped, and `Robot::run` returns a
+    /// timeout error once the rest of shutdown completes. When not set, the robot waits for all
+    /// callbacks to finish.
+    pub shutdown_timeout: Option<Duration>,
+    /// The address for the HTTP server to listen on.
+    ///
+    /// The server only runs if this is set, the `http` feature is enabled, and at least one route
+    /// was added with `Builder::http_route`.
+    pub http_address: Option<SocketAddr>,
 }
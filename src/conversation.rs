@@ -0,0 +1,74 @@
+//! Tracking of callbacks waiting for the next message in a conversation.
+
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot::{channel, Receiver, Sender};
+
+use crate::message::IncomingMessage;
+
+/// The callbacks waiting for the next message from a user in a room, shared by all messages the
+/// robot dispatches.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Conversations {
+    waiting: Arc<Mutex<Vec<Waiting>>>,
+}
+
+/// A callback waiting for the next message from a user in a room, or in direct messages if `room`
+/// is `None`, and in a thread within the room if `thread` is set.
+#[derive(Debug)]
+struct Waiting {
+    room: Option<String>,
+    sender: Sender<IncomingMessage>,
+    thread: Option<String>,
+    user: String,
+}
+
+impl Conversations {
+    /// Creates a new, empty `Conversations`.
+    pub(crate) fn new() -> Self {
+        Conversations::default()
+    }
+
+    /// Registers interest in the next message from the same user in the same room and thread as
+    /// `message`.
+    pub(crate) fn wait(&self, message: &IncomingMessage) -> Receiver<IncomingMessage> {
+        let (sender, receiver) = channel();
+
+        if let Ok(mut waiting) = self.waiting.lock() {
+            waiting.push(Waiting {
+                room: message.room().map(|room| room.id().to_owned()),
+                sender,
+                thread: message.thread().map(str::to_owned),
+                user: message.user().id().to_owned(),
+            });
+        }
+
+        receiver
+    }
+
+    /// Passes the message to the callback that has been waiting longest for it, if any.
+    ///
+    /// Returns the message back if no callback was waiting for it.
+    pub(crate) fn deliver(&self, message: IncomingMessage) -> Option<IncomingMessage> {
+        let mut waiting = match self.waiting.lock() {
+            Ok(waiting) => waiting,
+            Err(_) => return Some(message),
+        };
+
+        // Forget callbacks that stopped waiting, such as those that timed out.
+        waiting.retain(|waiting| !waiting.sender.is_canceled());
+
+        let room = message.room().map(|room| room.id());
+        let thread = message.thread();
+        let user = message.user().id();
+
+        match waiting.iter().position(|waiting| {
+            waiting.user == user
+                && waiting.room.as_deref() == room
+                && waiting.thread.as_deref() == thread
+        }) {
+            Some(index) => waiting.remove(index).sender.send(message).err(),
+            None => Some(message),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Structured references to users, rooms, and links within message bodies.
+//!
+//! Chat services that mark up mentions and links in their messages should attach them to
+//! `IncomingMessage`s with `IncomingMessage::with_entities`. Chat services that only deal in plain
+//! text can use `parse` to find the common `@user`, `#room`, and URL forms instead.
+
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::{room::Room, user::User};
+
+/// A user or room name, which may contain but not end with `.` or `-`.
+const NAME: &str = r"[[:alnum:]_](?:[[:alnum:]._-]*[[:alnum:]_])?";
+/// Characters that often follow a link in prose but are unlikely to be part of it.
+const URL_TRAILERS: &[char] = &['.', ',', ';', ':', '!', '?', '\'', '"', ')', ']', '>'];
+
+/// A user, room, or link referred to in the body of a message.
+#[derive(Clone, Debug)]
+pub struct Entity {
+    kind: EntityKind,
+    range: Range<usize>,
+}
+
+/// The thing an `Entity` refers to.
+#[derive(Clone, Debug)]
+pub enum EntityKind {
+    /// A mention of a user.
+    Mention(User),
+    /// A reference to a room.
+    Room(Room),
+    /// A link.
+    Url(String),
+}
+
+impl Entity {
+    /// Creates a new `Entity` found at the given byte range of the message's raw body.
+    pub fn new(kind: EntityKind, range: Range<usize>) -> Self {
+        Entity { kind, range }
+    }
+
+    /// What the entity refers to.
+    pub fn kind(&self) -> &EntityKind {
+        &self.kind
+    }
+
+    /// The byte range of the entity within the message's raw body, including any sigil such as
+    /// `@`.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+/// Finds the `@user` mentions, `#room` references, and `http` or `https` URLs in plain text.
+///
+/// Mentions and room references must begin the text or follow whitespace, so email addresses and
+/// fragments of URLs aren't mistaken for them. Since plain text carries no ids, the users and rooms
+/// found use their names as their ids.
+pub fn parse(text: &str) -> Vec<Entity> {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+
+    let regex = REGEX.get_or_init(|| {
+        let pattern = format!(
+            r"(?P<url>https?://\S+)|(?:\A|\s)(?:@(?P<user>{name})|#(?P<room>{name}))",
+            name = NAME
+        );
+
+        Regex::new(&pattern).expect("creating entity regex")
+    });
+
+    regex
+        .captures_iter(text)
+        .filter_map(|captures| {
+            if let Some(url) = captures.name("url") {
+                let trimmed = url.as_str().trim_end_matches(URL_TRAILERS);
+
+                Some(Entity::new(
+                    EntityKind::Url(trimmed.to_owned()),
+                    url.start()..url.start() + trimmed.len(),
+                ))
+            } else if let Some(user) = captures.name("user") {
+                let name = user.as_str();
+
+                Some(Entity::new(
+                    EntityKind::Mention(User::new(name, Some(name), None)),
+                    user.start() - 1..user.end(),
+                ))
+            } else {
+                captures.name("room").map(|room| {
+                    Entity::new(
+                        EntityKind::Room(Room::new(room.as_str(), Some(room.as_str()))),
+                        room.start() - 1..room.end(),
+                    )
+                })
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, EntityKind};
+
+    #[test]
+    fn parses_mentions_rooms_and_urls() {
+        let text = "@alice: see https://ci.example.com/42?log=1. in #ops, cc @bob.";
+        let entities = parse(text);
+        let found: Vec<(String, &str)> = entities
+            .iter()
+            .map(|entity| {
+                let kind = match entity.kind() {
+                    EntityKind::Mention(user) => format!("user {}", user.id()),
+                    EntityKind::Room(room) => format!("room {}", room.id()),
+                    EntityKind::Url(url) => format!("url {}", url),
+                };
+
+                (kind, &text[entity.range()])
+            })
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("user alice".to_owned(), "@alice"),
+                (
+                    "url https://ci.example.com/42?log=1".to_owned(),
+                    "https://ci.example.com/42?log=1"
+                ),
+                ("room ops".to_owned(), "#ops"),
+                ("user bob".to_owned(), "@bob"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_emails_and_fragments() {
+        let entities = parse("mail alice@example.com about https://example.com/#top in #ops");
+
+        assert_eq!(entities.len(), 2);
+        assert!(matches!(entities[0].kind(), EntityKind::Url(url) if url.ends_with("#top")));
+        assert!(matches!(entities[1].kind(), EntityKind::Room(room) if room.id() == "ops"));
+    }
+}
@@ -0,0 +1,60 @@
+//! Types for reacting to events from the chat service other than messages.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::Stream;
+
+use crate::{
+    callback::CallbackFuture,
+    reaction::Reaction,
+    result::Error,
+    room::Room,
+    store::Store,
+    user::User,
+};
+
+/// Something that happened on the chat service, other than a message being sent.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// The robot connected to the chat service.
+    Connected,
+    /// The robot disconnected from the chat service.
+    Disconnected,
+    /// The robot joined a room.
+    RoomJoined(Room),
+    /// The robot left a room.
+    RoomLeft(Room),
+    /// A user joined a room.
+    UserJoined(User, Room),
+    /// A user left a room.
+    UserLeft(User, Room),
+    /// A room's topic was changed, optionally by a known user.
+    TopicChanged(Room, String, Option<User>),
+    /// A user added an emoji reaction to a message.
+    ReactionAdded(Reaction),
+    /// A user removed an emoji reaction from a message.
+    ReactionRemoved(Reaction),
+}
+
+/// A handler that reacts to events from the chat service.
+///
+/// Event handlers are registered with `Builder::on_event` and receive every event. When multiple
+/// handlers are registered, they are invoked in the order they were added.
+pub trait EventHandler<C, S> {
+    /// Invokes the handler with the event that occurred.
+    fn call(&self, chat: Arc<C>, event: &Event, store: S) -> CallbackFuture;
+}
+
+impl<F, C, S> EventHandler<C, S> for F
+where
+    F: Fn(Arc<C>, &Event, S) -> CallbackFuture,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, event: &Event, store: S) -> CallbackFuture {
+        self(chat, event, store)
+    }
+}
+
+/// An asynchronous stream of events.
+pub type Events = Pin<Box<dyn Stream<Item = Result<Event, Error>>>>;
@@ -0,0 +1,506 @@
+//! Types for formatting outgoing messages.
+//!
+//! A `RichText` describes formatted text independently of any chat service. Chat services render
+//! it to their native markup with a `Renderer`, such as `Markdown` or `Irc`, and any service can
+//! fall back to `PlainText`.
+//!
+//! # Examples
+//!
+//! ```
+//! use rustin::format::{Markdown, PlainText, Renderer, RichText};
+//!
+//! let text = RichText::new()
+//!     .text("Deployed ")
+//!     .bold("api")
+//!     .text(" to ")
+//!     .code("prod")
+//!     .list(vec!["migrations ran", "caches cleared"]);
+//!
+//! assert_eq!(
+//!     Markdown.render(&text),
+//!     "Deployed **api** to `prod`\n\n- migrations ran\n- caches cleared"
+//! );
+//! assert_eq!(
+//!     PlainText.render(&text),
+//!     "Deployed api to prod\n- migrations ran\n- caches cleared"
+//! );
+//! ```
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::user::User;
+
+/// Formatted text for an outgoing message.
+#[derive(Clone, Debug, Default)]
+pub struct RichText {
+    nodes: Vec<Node>,
+}
+
+/// A piece of formatted text.
+///
+/// Code blocks, lists, and quotes are blocks, which are always rendered on lines of their own.
+#[derive(Clone, Debug)]
+pub enum Node {
+    /// Unformatted text.
+    Text(String),
+    /// Bold text.
+    Bold(RichText),
+    /// Italic text.
+    Italic(RichText),
+    /// Inline code.
+    Code(String),
+    /// A block of code, optionally in a specific language.
+    CodeBlock {
+        /// The code.
+        code: String,
+        /// The language of the code, if known.
+        language: Option<String>,
+    },
+    /// A link.
+    Link {
+        /// The text of the link.
+        text: RichText,
+        /// The URL the link points to.
+        url: String,
+    },
+    /// A mention of a user.
+    Mention(User),
+    /// A list of items.
+    List {
+        /// The items in the list.
+        items: Vec<RichText>,
+        /// Whether the items are numbered.
+        ordered: bool,
+    },
+    /// A quotation.
+    Quote(RichText),
+}
+
+impl Node {
+    /// Whether the node is rendered on lines of its own.
+    fn is_block(&self) -> bool {
+        matches!(
+            *self,
+            Node::CodeBlock { .. } | Node::List { .. } | Node::Quote(_)
+        )
+    }
+}
+
+impl RichText {
+    /// Creates a new, empty `RichText`.
+    pub fn new() -> Self {
+        RichText::default()
+    }
+
+    /// Appends a node.
+    pub fn push(mut self, node: Node) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Appends unformatted text.
+    pub fn text<T>(self, text: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.push(Node::Text(text.into()))
+    }
+
+    /// Appends bold text.
+    pub fn bold<T>(self, text: T) -> Self
+    where
+        T: Into<RichText>,
+    {
+        self.push(Node::Bold(text.into()))
+    }
+
+    /// Appends italic text.
+    pub fn italic<T>(self, text: T) -> Self
+    where
+        T: Into<RichText>,
+    {
+        self.push(Node::Italic(text.into()))
+    }
+
+    /// Appends inline code.
+    pub fn code<T>(self, code: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.push(Node::Code(code.into()))
+    }
+
+    /// Appends a block of code, optionally in a specific language.
+    pub fn code_block<T>(self, code: T, language: Option<&str>) -> Self
+    where
+        T: Into<String>,
+    {
+        self.push(Node::CodeBlock {
+            code: code.into(),
+            language: language.map(str::to_owned),
+        })
+    }
+
+    /// Appends a link.
+    pub fn link<T, U>(self, text: T, url: U) -> Self
+    where
+        T: Into<RichText>,
+        U: Into<String>,
+    {
+        self.push(Node::Link {
+            text: text.into(),
+            url: url.into(),
+        })
+    }
+
+    /// Appends a mention of a user.
+    pub fn mention(self, user: &User) -> Self {
+        self.push(Node::Mention(user.clone()))
+    }
+
+    /// Appends a bulleted list.
+    pub fn list<I, T>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<RichText>,
+    {
+        self.push(Node::List {
+            items: items.into_iter().map(Into::into).collect(),
+            ordered: false,
+        })
+    }
+
+    /// Appends a numbered list.
+    pub fn ordered_list<I, T>(self, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<RichText>,
+    {
+        self.push(Node::List {
+            items: items.into_iter().map(Into::into).collect(),
+            ordered: true,
+        })
+    }
+
+    /// Appends a quotation.
+    pub fn quote<T>(self, text: T) -> Self
+    where
+        T: Into<RichText>,
+    {
+        self.push(Node::Quote(text.into()))
+    }
+
+    /// The nodes that make up the text.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+}
+
+impl<'a> From<&'a str> for RichText {
+    fn from(text: &'a str) -> Self {
+        RichText::new().text(text)
+    }
+}
+
+impl From<String> for RichText {
+    fn from(text: String) -> Self {
+        RichText::new().text(text)
+    }
+}
+
+impl Display for RichText {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", PlainText.render(self))
+    }
+}
+
+/// Renders `RichText` to a chat service's native markup.
+///
+/// Every method has a default implementation producing plain text, so a renderer only needs to
+/// override the formatting its chat service supports.
+pub trait Renderer {
+    /// Renders unformatted text, escaping any characters with special meaning in the markup.
+    fn text(&self, text: &str) -> String {
+        text.to_owned()
+    }
+
+    /// Renders bold text, given its already rendered contents.
+    fn bold(&self, text: String) -> String {
+        text
+    }
+
+    /// Renders italic text, given its already rendered contents.
+    fn italic(&self, text: String) -> String {
+        text
+    }
+
+    /// Renders inline code.
+    fn code(&self, code: &str) -> String {
+        code.to_owned()
+    }
+
+    /// Renders a block of code.
+    fn code_block(&self, code: &str, _language: Option<&str>) -> String {
+        code.trim_end_matches('\n').to_owned()
+    }
+
+    /// Renders a link, given its already rendered text.
+    fn link(&self, text: String, url: &str) -> String {
+        if text == url {
+            text
+        } else {
+            format!("{} ({})", text, url)
+        }
+    }
+
+    /// Renders a mention of a user.
+    fn mention(&self, user: &User) -> String {
+        format!("@{}", user.username().unwrap_or_else(|| user.id()))
+    }
+
+    /// Renders an item of a list, given its already rendered contents and, for numbered lists,
+    /// its number.
+    fn list_item(&self, text: String, number: Option<usize>) -> String {
+        match number {
+            Some(number) => format!("{}. {}", number, text),
+            None => format!("- {}", text),
+        }
+    }
+
+    /// Renders a quotation, given its already rendered contents.
+    fn quote(&self, text: String) -> String {
+        text.lines()
+            .map(|line| format!("> {}", line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The text placed between a block and the text around it, which by default starts a new line.
+    fn block_separator(&self) -> &str {
+        "\n"
+    }
+
+    /// Renders the text, placing each block on lines of its own.
+    fn render(&self, text: &RichText) -> String {
+        let mut output = String::new();
+        let mut after_block = false;
+
+        for node in text.nodes() {
+            let block = node.is_block();
+
+            if (block || after_block) && !output.is_empty() {
+                output.truncate(output.trim_end_matches('\n').len());
+                output.push_str(self.block_separator());
+            }
+
+            output.push_str(&self.render_node(node));
+            after_block = block;
+        }
+
+        output
+    }
+
+    /// Renders a single node.
+    fn render_node(&self, node: &Node) -> String {
+        match *node {
+            Node::Text(ref text) => self.text(text),
+            Node::Bold(ref text) => self.bold(self.render(text)),
+            Node::Italic(ref text) => self.italic(self.render(text)),
+            Node::Code(ref code) => self.code(code),
+            Node::CodeBlock {
+                ref code,
+                ref language,
+            } => self.code_block(code, language.as_deref()),
+            Node::Link { ref text, ref url } => self.link(self.render(text), url),
+            Node::Mention(ref user) => self.mention(user),
+            Node::List { ref items, ordered } => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    self.list_item(
+                        self.render(item),
+                        if ordered { Some(index + 1) } else { None },
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Node::Quote(ref text) => self.quote(self.render(text)),
+        }
+    }
+}
+
+/// Renders `RichText` as plain text, without any markup.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlainText;
+
+impl Renderer for PlainText {}
+
+/// Renders `RichText` as Markdown.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Markdown;
+
+impl Renderer for Markdown {
+    /// Separates blocks with a blank line, so text after a block isn't taken as part of it.
+    fn block_separator(&self) -> &str {
+        "\n\n"
+    }
+
+    fn text(&self, text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+
+        for character in text.chars() {
+            if "\\`*_[]<>#".contains(character) {
+                escaped.push('\\');
+            }
+
+            escaped.push(character);
+        }
+
+        escaped
+    }
+
+    fn bold(&self, text: String) -> String {
+        format!("**{}**", text)
+    }
+
+    fn italic(&self, text: String) -> String {
+        format!("_{}_", text)
+    }
+
+    fn code(&self, code: &str) -> String {
+        // Use a longer run of backticks than any in the code itself.
+        let mut fence = "`".to_owned();
+
+        while code.contains(&fence) {
+            fence.push('`');
+        }
+
+        if fence.len() > 1 || code.starts_with('`') || code.ends_with('`') {
+            format!("{} {} {}", fence, code, fence)
+        } else {
+            format!("{}{}{}", fence, code, fence)
+        }
+    }
+
+    fn code_block(&self, code: &str, language: Option<&str>) -> String {
+        let mut fence = "```".to_owned();
+
+        while code.contains(&fence) {
+            fence.push('`');
+        }
+
+        format!(
+            "{}{}\n{}\n{}",
+            fence,
+            language.unwrap_or(""),
+            code.trim_end_matches('\n'),
+            fence
+        )
+    }
+
+    fn link(&self, text: String, url: &str) -> String {
+        format!("[{}]({})", text, url)
+    }
+}
+
+/// Renders `RichText` with IRC formatting control codes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Irc;
+
+const IRC_BOLD: char = '\x02';
+const IRC_ITALIC: char = '\x1d';
+const IRC_MONOSPACE: char = '\x11';
+
+impl Renderer for Irc {
+    fn bold(&self, text: String) -> String {
+        format!("{}{}{}", IRC_BOLD, text, IRC_BOLD)
+    }
+
+    fn italic(&self, text: String) -> String {
+        format!("{}{}{}", IRC_ITALIC, text, IRC_ITALIC)
+    }
+
+    fn code(&self, code: &str) -> String {
+        format!("{}{}{}", IRC_MONOSPACE, code, IRC_MONOSPACE)
+    }
+
+    fn code_block(&self, code: &str, _language: Option<&str>) -> String {
+        code.trim_end_matches('\n')
+            .lines()
+            .map(|line| self.code(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn mention(&self, user: &User) -> String {
+        user.username().unwrap_or_else(|| user.id()).to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Irc, Markdown, PlainText, Renderer, RichText};
+    use crate::user::User;
+
+    fn example() -> RichText {
+        let alice = User::new("2", Some("alice"), None);
+
+        RichText::new()
+            .mention(&alice)
+            .text(": ")
+            .bold(RichText::new().text("build ").italic("#42"))
+            .text(" failed, see ")
+            .link("the logs", "https://ci.example.com/42")
+            .code_block("error[E0308]: mismatched types\n", Some("text"))
+            .quote("It works on my machine.\nReally.")
+            .ordered_list(vec!["fix it", "ship it"])
+            .text("Thanks!")
+    }
+
+    #[test]
+    fn renders_plain_text() {
+        assert_eq!(
+            PlainText.render(&example()),
+            "@alice: build #42 failed, see the logs (https://ci.example.com/42)\n\
+             error[E0308]: mismatched types\n\
+             > It works on my machine.\n\
+             > Really.\n\
+             1. fix it\n\
+             2. ship it\n\
+             Thanks!"
+        );
+    }
+
+    #[test]
+    fn renders_markdown() {
+        assert_eq!(
+            Markdown.render(&example()),
+            "@alice: **build _\\#42_** failed, see [the logs](https://ci.example.com/42)\n\
+             \n\
+             ```text\n\
+             error[E0308]: mismatched types\n\
+             ```\n\
+             \n\
+             > It works on my machine.\n\
+             > Really.\n\
+             \n\
+             1. fix it\n\
+             2. ship it\n\
+             \n\
+             Thanks!"
+        );
+        assert_eq!(Markdown.code("a`b"), "`` a`b ``");
+    }
+
+    #[test]
+    fn renders_irc() {
+        assert_eq!(
+            Irc.render(&RichText::new().bold("hi").text(" ").mention(&User::new(
+                "1",
+                Some("bob"),
+                None
+            ))),
+            "\x02hi\x02 bob"
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! The built-in `help` command.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::{
+    authorization::Authorization,
+    callback::{Callback, CallbackFuture},
+    chat_service::{Capability, ChatService},
+    message::IncomingMessage,
+    route::Route,
+    store::Store,
+};
+
+const DESCRIPTION: &str = "Lists the available commands, optionally only those matching a term.";
+const PATTERN: &str = r"(?i)\A\s*help(?:\s+(?P<term>.*?))?\s*\z";
+const USAGE: &str = "help [term]";
+
+/// Creates the route for the `help` command, listing the directed routes in `routes`.
+///
+/// Routes that require an authorization group are only listed for members of the group. When asked
+/// in a room, the list is sent to the thread the question was asked in or as a direct message, if
+/// the chat service supports them, to avoid flooding the room.
+pub(crate) fn route<C, S>(routes: &[Route<C, S>], authorization: &Authorization<S>) -> Route<C, S>
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    let mut entries: Vec<Entry> = routes
+        .iter()
+        .filter(|route| !route.eavesdrop())
+        .map(Entry::from)
+        .collect();
+
+    entries.push(Entry {
+        description: Some(DESCRIPTION.to_owned()),
+        group: None,
+        usage: vec![USAGE.to_owned()],
+    });
+
+    let help = Help {
+        authorization: authorization.clone(),
+        entries: Arc::new(entries),
+        pattern: Regex::new(PATTERN).expect("creating help regex"),
+    };
+
+    Route::new(PATTERN, false, "help", help)
+        .expect("creating help route")
+        .with_help(DESCRIPTION)
+        .with_example(USAGE)
+}
+
+/// A directed route as listed by the `help` command.
+#[derive(Clone, Debug)]
+struct Entry {
+    description: Option<String>,
+    group: Option<String>,
+    usage: Vec<String>,
+}
+
+impl Entry {
+    /// Whether the entry's usage or description contains the given lowercase search term.
+    fn matches(&self, term: &str) -> bool {
+        self.usage
+            .iter()
+            .chain(self.description.iter())
+            .any(|text| text.to_lowercase().contains(term))
+    }
+}
+
+impl<'a, C, S> From<&'a Route<C, S>> for Entry
+where
+    C: ChatService,
+{
+    fn from(route: &'a Route<C, S>) -> Self {
+        let usage = if route.examples().is_empty() {
+            vec![route.pattern().as_str().to_owned()]
+        } else {
+            route.examples().to_vec()
+        };
+
+        Entry {
+            description: route.help().map(str::to_owned),
+            group: route.required_group().map(str::to_owned),
+            usage,
+        }
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.usage.join(", "))?;
+
+        if let Some(ref description) = self.description {
+            write!(f, " - {}", description)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The callback for the `help` command.
+struct Help<S>
+where
+    S: Store,
+{
+    authorization: Authorization<S>,
+    entries: Arc<Vec<Entry>>,
+    pattern: Regex,
+}
+
+impl<C, S> Callback<C, S> for Help<S>
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, message: &IncomingMessage, _store: S) -> CallbackFuture {
+        let term = self
+            .pattern
+            .captures(message.body())
+            .and_then(|captures| captures.name("term"))
+            .map(|term| term.as_str().to_lowercase())
+            .filter(|term| !term.is_empty());
+
+        let authorization = self.authorization.clone();
+        let entries = self.entries.clone();
+        let message = message.clone();
+
+        Box::pin(async move {
+            let mut lines = Vec::new();
+
+            for entry in entries.iter() {
+                if term.as_ref().is_some_and(|term| !entry.matches(term)) {
+                    continue;
+                }
+
+                if let Some(ref group) = entry.group {
+                    if !authorization.is_member(message.user().id(), group).await? {
+                        continue;
+                    }
+                }
+
+                lines.push(entry.to_string());
+            }
+
+            let body = match term {
+                _ if !lines.is_empty() => lines.join("\n"),
+                Some(term) => format!("No commands match \"{}\".", term),
+                None => "There are no commands available.".to_owned(),
+            };
+
+            let capabilities = chat.capabilities();
+            let reply = if message.room().is_none() {
+                message.reply(body)
+            } else if message.thread().is_some() && capabilities.contains(&Capability::Threads) {
+                message.reply_in_thread(body)
+            } else if capabilities.contains(&Capability::DirectMessages) {
+                message.reply_privately(body)
+            } else {
+                message.reply(body)
+            };
+
+            chat.send_message(reply).await
+        })
+    }
+}
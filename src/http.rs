@@ -0,0 +1,329 @@
+//! An embedded HTTP server for receiving webhooks, available with the `http` feature.
+//!
+//! Routes for the server are added with `Builder::http_route`, and the server listens on
+//! `Config::http_address`. Each request is passed to the handler for the first route matching its
+//! method and path. Handlers receive the chat service and the store, so they can post messages to
+//! chat or record data for other routes to use.
+//!
+//! Requests with bodies larger than 1 MiB are rejected with a `413` response, and at most eight
+//! requests are handled at once. Further requests wait for one of those to finish.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use rustin::{
+//!     chat_service::{Chat, Shell},
+//!     http::{HandlerFuture, Request, Response},
+//!     message::{OutgoingMessage, Target},
+//!     prelude::*,
+//!     room::Room,
+//!     store::Memory,
+//! };
+//!
+//! fn build_finished(chat: Arc<Chat<Shell>>, request: &Request, _store: Memory) -> HandlerFuture {
+//!     let room = Room::new("builds", None::<String>);
+//!     let body = request.text().map(|text| format!("Build finished: {}", text));
+//!
+//!     Box::pin(async move {
+//!         chat.send_message(OutgoingMessage::new(Target::Room(room), body?))
+//!             .await?;
+//!
+//!         Ok(Response::ok())
+//!     })
+//! }
+//!
+//! let config = Config {
+//!     http_address: Some("127.0.0.1:8080".parse().unwrap()),
+//!     ..Config::default()
+//! };
+//!
+//! let robot = Robot::build(config, Shell::default(), Memory::new())
+//!     .http_route("POST", "/builds", build_finished)
+//!     .finish();
+//! ```
+
+use std::future::Future;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::str;
+use std::sync::Arc;
+use std::thread;
+
+use futures::{
+    channel::{mpsc::unbounded, oneshot},
+    executor::block_on,
+    future::{select, FutureExt},
+    stream::StreamExt,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{cancellation::Cancelled, result::Error, store::Store};
+
+/// The largest request body the server accepts, in bytes.
+pub(crate) const MAX_BODY_LENGTH: usize = 1024 * 1024;
+/// The number of threads reading requests, which limits how many are handled at once.
+const WORKERS: usize = 8;
+
+/// A handler for HTTP requests.
+pub trait Handler<C, S> {
+    /// Handles the request, producing the response to send back to the client.
+    fn call(&self, chat: Arc<C>, request: &Request, store: S) -> HandlerFuture;
+}
+
+impl<F, C, S> Handler<C, S> for F
+where
+    F: Fn(Arc<C>, &Request, S) -> HandlerFuture,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, request: &Request, store: S) -> HandlerFuture {
+        self(chat, request, store)
+    }
+}
+
+/// The type returned by HTTP handlers.
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Result<Response, Error>>>>;
+
+/// An HTTP request received by the server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    body: Vec<u8>,
+    headers: Vec<(String, String)>,
+    method: String,
+    path: String,
+    query: Option<String>,
+}
+
+impl Request {
+    /// Reads a request from the underlying server, or returns the response to reject it with.
+    fn read(request: &mut tiny_http::Request) -> Result<Self, Response> {
+        if request.body_length().unwrap_or(0) > MAX_BODY_LENGTH {
+            return Err(too_large());
+        }
+
+        let mut body = Vec::new();
+
+        // Chunked requests don't declare their length, so stop reading once past the limit.
+        request
+            .as_reader()
+            .take(MAX_BODY_LENGTH as u64 + 1)
+            .read_to_end(&mut body)
+            .map_err(|_| Response::new(400, "Bad Request"))?;
+
+        if body.len() > MAX_BODY_LENGTH {
+            return Err(too_large());
+        }
+
+        let (path, query) = match request.url().find('?') {
+            Some(index) => (
+                request.url()[..index].to_owned(),
+                Some(request.url()[index + 1..].to_owned()),
+            ),
+            None => (request.url().to_owned(), None),
+        };
+
+        Ok(Request {
+            body,
+            headers: request
+                .headers()
+                .iter()
+                .map(|header| (header.field.to_string(), header.value.to_string()))
+                .collect(),
+            method: request.method().to_string(),
+            path,
+            query,
+        })
+    }
+
+    /// The request's method, such as `POST`.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// The path of the request's URL, without the query string.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The query string of the request's URL, if any.
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// The value of the header with the given name, if present. Header names are
+    /// case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The raw body of the request.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The body of the request as text.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body is not valid UTF-8.
+    pub fn text(&self) -> Result<&str, Error> {
+        str::from_utf8(&self.body).map_err(|error| Error::custom(error.to_string()))
+    }
+
+    /// Deserializes the body of the request from JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body is not valid JSON for `T`.
+    pub fn json<T>(&self) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+/// A plain text response to an HTTP request.
+#[derive(Clone, Debug)]
+pub struct Response {
+    body: String,
+    status: u16,
+}
+
+impl Response {
+    /// Creates a new `Response` with the given status code and body.
+    pub fn new<B>(status: u16, body: B) -> Self
+    where
+        B: Into<String>,
+    {
+        Response {
+            body: body.into(),
+            status,
+        }
+    }
+
+    /// Creates a `200 OK` response.
+    pub fn ok() -> Self {
+        Response::new(200, "OK")
+    }
+
+    /// The response's status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// The body of the response.
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+/// A handler paired with the method and path of the requests it handles.
+pub(crate) struct Endpoint<C, S> {
+    pub(crate) handler: Box<dyn Handler<C, S>>,
+    pub(crate) method: String,
+    pub(crate) path: String,
+}
+
+/// Runs the HTTP server until `cancelled` completes.
+///
+/// The server itself runs on a fixed number of separate threads, each reading one request at a
+/// time and passing it to the robot over a channel so that handlers run alongside the robot's
+/// other callbacks.
+pub(crate) async fn serve<C, S>(
+    address: SocketAddr,
+    endpoints: &[Endpoint<C, S>],
+    chat: Arc<C>,
+    store: S,
+    cancelled: Cancelled,
+) -> Result<(), Error>
+where
+    S: Store,
+{
+    let server = tiny_http::Server::http(address)
+        .map_err(|error| Error::custom(format!("starting HTTP server: {}", error)))?;
+    let server = Arc::new(server);
+    let (tx, rx) = unbounded();
+
+    for _ in 0..WORKERS {
+        let listener = server.clone();
+        let tx = tx.clone();
+
+        thread::spawn(move || {
+            for mut raw in listener.incoming_requests() {
+                let response = match Request::read(&mut raw) {
+                    Ok(request) => {
+                        let (respond, response) = oneshot::channel();
+
+                        match tx.unbounded_send((request, respond)) {
+                            Ok(()) => block_on(response).unwrap_or_else(|_| unavailable()),
+                            Err(_) => unavailable(),
+                        }
+                    }
+                    Err(response) => response,
+                };
+
+                let _ = raw.respond(
+                    tiny_http::Response::from_string(response.body)
+                        .with_status_code(response.status),
+                );
+            }
+        });
+    }
+
+    let requests = rx.for_each_concurrent(None, |(request, respond)| {
+        handle(endpoints, chat.clone(), request, store.clone()).map(|response| {
+            let _ = respond.send(response);
+        })
+    });
+
+    select(Box::pin(requests), cancelled).await;
+
+    // Each call only stops one of the threads.
+    for _ in 0..WORKERS {
+        server.unblock();
+    }
+
+    Ok(())
+}
+
+/// Passes a request to the matching endpoint's handler.
+///
+/// Errors from handlers are reported to the client as a `500` response rather than stopping the
+/// robot.
+async fn handle<C, S>(
+    endpoints: &[Endpoint<C, S>],
+    chat: Arc<C>,
+    request: Request,
+    store: S,
+) -> Response {
+    let mut routes = endpoints
+        .iter()
+        .filter(|endpoint| endpoint.path == request.path)
+        .peekable();
+
+    if routes.peek().is_none() {
+        return Response::new(404, "Not Found");
+    }
+
+    match routes.find(|endpoint| endpoint.method.eq_ignore_ascii_case(&request.method)) {
+        Some(endpoint) => match endpoint.handler.call(chat, &request, store).await {
+            Ok(response) => response,
+            Err(_) => Response::new(500, "Internal Server Error"),
+        },
+        None => Response::new(405, "Method Not Allowed"),
+    }
+}
+
+fn too_large() -> Response {
+    Response::new(413, "Payload Too Large")
+}
+
+fn unavailable() -> Response {
+    Response::new(503, "Service Unavailable")
+}
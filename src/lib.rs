@@ -2,32 +2,73 @@
 
 #![deny(missing_docs)]
 
+pub mod authorization;
 pub mod callback;
+pub mod cancellation;
 pub mod chat_service;
+pub mod command;
 pub mod config;
+mod conversation;
+pub mod entity;
+pub mod event;
+pub mod format;
+mod help;
+#[cfg(feature = "http")]
+pub mod http;
 pub mod message;
+pub mod middleware;
+pub mod plugin;
 pub mod prelude;
+pub mod reaction;
+pub mod reminders;
 pub mod result;
 pub mod robot;
 pub mod room;
 pub mod route;
+pub mod schedule;
 pub mod store;
 pub mod user;
 
 #[cfg(test)]
 mod tests {
-    use std::{pin::Pin, sync::Arc};
+    use std::{
+        fmt::Display,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::Poll,
+        time::{Duration, Instant},
+    };
 
+    use chrono::{TimeZone, Utc};
     use futures::{
-        future::{ok, Future},
-        stream::empty,
+        executor::block_on,
+        future::{err, join, ok, poll_fn, Future},
+        stream::{empty, iter, pending, StreamExt},
     };
+    use futures_timer::Delay;
 
     use super::{
-        chat_service::Incoming,
-        message::OutgoingMessage,
+        authorization::Authorization,
+        chat_service::{
+            Capabilities,
+            Capability,
+            Chat,
+            EditingChatService,
+            Incoming,
+            PresenceChatService,
+            ReactingChatService,
+            Sent,
+        },
+        event::{Event, Events},
+        format::{Markdown, Renderer, RichText},
+        message::{Attachment, MessageId, OutgoingMessage, Source, Target},
         prelude::*,
-        store::Memory,
+        reaction::Reaction,
+        reminders::Reminders,
+        robot::Builder,
+        room::Room,
+        schedule::{ManualClock, Schedule},
+        store::{Memory, ScopedStore, StoreFuture},
         user::User,
     };
 
@@ -48,6 +89,284 @@ mod tests {
         }
     }
 
+    /// A chat service that yields a fixed set of messages and records the bodies of replies.
+    #[derive(Clone, Debug, Default)]
+    struct TestChat {
+        capabilities: Capabilities,
+        events: Arc<Mutex<Vec<Event>>>,
+        incoming: Arc<Mutex<Vec<IncomingMessage>>>,
+        log: Arc<Mutex<Vec<String>>>,
+        max_message_length: Option<usize>,
+        sent: Arc<Mutex<Vec<OutgoingMessage>>>,
+        stay_open: bool,
+    }
+
+    impl TestChat {
+        fn new(messages: Vec<IncomingMessage>) -> Self {
+            TestChat {
+                incoming: Arc::new(Mutex::new(messages)),
+                ..TestChat::default()
+            }
+        }
+
+        /// Declares support for the given capabilities.
+        fn capabilities(mut self, capabilities: &[Capability]) -> Self {
+            self.capabilities = capabilities.iter().cloned().collect();
+            self
+        }
+
+        /// Limits the length of messages, as measured when rendered as Markdown.
+        fn max_message_length(mut self, max_length: usize) -> Self {
+            self.max_message_length = Some(max_length);
+            self
+        }
+
+        /// Yields the events before any incoming messages.
+        fn events(self, events: Vec<Event>) -> Self {
+            *self.events.lock().unwrap() = events;
+            self
+        }
+
+        /// Keeps the incoming stream open after the fixed messages have been yielded.
+        fn stay_open(mut self) -> Self {
+            self.stay_open = true;
+            self
+        }
+
+        fn log(&self) -> Vec<String> {
+            self.log.lock().unwrap().clone()
+        }
+
+        fn sent(&self) -> Vec<String> {
+            self.sent
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|message| message.body().to_owned())
+                .collect()
+        }
+
+        fn targets(&self) -> Vec<Target> {
+            self.sent
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|message| message.target().clone())
+                .collect()
+        }
+    }
+
+    impl ChatService for TestChat {
+        fn send_message(&self, message: OutgoingMessage) -> Success {
+            if let Target::Room(room) = message.target() {
+                if room.id() == "archived" {
+                    self.log.lock().unwrap().push("send failed".to_owned());
+
+                    return Box::pin(err(Error::custom("room is archived")));
+                }
+            }
+
+            self.sent.lock().unwrap().push(message);
+
+            Box::pin(ok(()))
+        }
+
+        fn capabilities(&self) -> Capabilities {
+            self.capabilities.clone()
+        }
+
+        fn max_message_length(&self) -> Option<usize> {
+            self.max_message_length
+        }
+
+        fn renderer(&self) -> &dyn Renderer {
+            &Markdown
+        }
+
+        fn events(&self) -> Events {
+            let events: Vec<Event> = self.events.lock().unwrap().drain(..).collect();
+
+            Box::pin(iter(events.into_iter().map(Ok)))
+        }
+
+        fn incoming(&self, _alias: Option<String>) -> Incoming {
+            let messages: Vec<IncomingMessage> = self.incoming.lock().unwrap().drain(..).collect();
+            let messages = iter(messages.into_iter().map(Ok));
+
+            if self.stay_open {
+                Box::pin(messages.chain(pending()))
+            } else {
+                Box::pin(messages)
+            }
+        }
+
+        fn user(&self) -> Pin<Box<dyn Future<Output = Result<User, Error>>>> {
+            Box::pin(ok(User::new("robot", None, None)))
+        }
+
+        fn shutdown(&self) -> Success {
+            self.log.lock().unwrap().push("adapter shutdown".to_owned());
+
+            Box::pin(ok(()))
+        }
+    }
+
+    impl EditingChatService for TestChat {
+        fn send_message_with_id(&self, message: OutgoingMessage) -> Sent {
+            let mut sent = self.sent.lock().unwrap();
+
+            sent.push(message);
+
+            Box::pin(ok(MessageId::new((sent.len() - 1).to_string())))
+        }
+
+        fn edit_message(&self, id: &MessageId, message: OutgoingMessage) -> Success {
+            let index: usize = id.as_str().parse().unwrap();
+
+            self.sent.lock().unwrap()[index] = message;
+
+            Box::pin(ok(()))
+        }
+
+        fn delete_message(&self, id: &MessageId) -> Success {
+            self.log.lock().unwrap().push(format!("deleted {}", id));
+
+            Box::pin(ok(()))
+        }
+    }
+
+    impl ReactingChatService for TestChat {
+        fn add_reaction(&self, message: &MessageId, emoji: &str) -> Success {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("reacted {} to {}", emoji, message));
+
+            Box::pin(ok(()))
+        }
+
+        fn remove_reaction(&self, message: &MessageId, emoji: &str) -> Success {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("unreacted {} to {}", emoji, message));
+
+            Box::pin(ok(()))
+        }
+    }
+
+    impl PresenceChatService for TestChat {
+        fn start_typing(&self, target: &Target) -> Success {
+            if let Target::User(_) = target {
+                return Box::pin(err(Error::custom("typing unsupported in DMs")));
+            }
+
+            self.log.lock().unwrap().push("typing".to_owned());
+
+            Box::pin(ok(()))
+        }
+
+        fn stop_typing(&self, target: &Target) -> Success {
+            if let Target::User(_) = target {
+                return Box::pin(err(Error::custom("typing unsupported in DMs")));
+            }
+
+            self.log.lock().unwrap().push("stopped typing".to_owned());
+
+            Box::pin(ok(()))
+        }
+    }
+
+    /// A store that waits briefly before each operation, like a store backed by a database.
+    #[derive(Clone, Debug, Default)]
+    struct SlowStore(Memory);
+
+    impl Store for SlowStore {
+        type Error = Error;
+
+        fn get<K>(&self, key: K) -> StoreFuture<Option<String>, Self::Error>
+        where
+            K: AsRef<str> + Display,
+        {
+            let store = self.0.clone();
+            let key = key.as_ref().to_owned();
+
+            Box::pin(async move {
+                Delay::new(Duration::from_millis(1)).await;
+                store.get(key).await
+            })
+        }
+
+        fn set<K, V>(&self, key: K, value: V) -> StoreFuture<(), Self::Error>
+        where
+            K: Display + Into<String>,
+            V: Into<String>,
+        {
+            let store = self.0.clone();
+            let (key, value) = (key.into(), value.into());
+
+            Box::pin(async move {
+                Delay::new(Duration::from_millis(1)).await;
+                store.set(key, value).await
+            })
+        }
+
+        fn scoped<P>(&self, prefix: P) -> ScopedStore<Self>
+        where
+            P: Into<String>,
+        {
+            ScopedStore::new(self.clone(), prefix)
+        }
+    }
+
+    fn alice() -> User {
+        User::new("2", Some("alice"), None)
+    }
+
+    fn direct(body: &str) -> IncomingMessage {
+        IncomingMessage::new(Source::User(alice()), body.to_owned(), 0)
+    }
+
+    fn in_room(room: &str, body: &str, mention_offset: usize) -> IncomingMessage {
+        let source = Source::UserInRoom(alice(), Room::new(room, None::<String>));
+
+        IncomingMessage::new(source, body.to_owned(), mention_offset)
+    }
+
+    fn echo<C, S>(chat: Arc<C>, message: &IncomingMessage, _store: S) -> Success
+    where
+        C: ChatService,
+        S: Store,
+    {
+        chat.send_message(message.reply(message.body()))
+    }
+
+    /// Lets other futures run before continuing.
+    async fn yield_now() {
+        let mut yielded = false;
+
+        poll_fn(|cx| {
+            if yielded {
+                Poll::Ready(())
+            } else {
+                yielded = true;
+                cx.waker().wake_by_ref();
+
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    fn run<S>(chat: &TestChat, builder: Builder<Chat<TestChat>, S>) -> Vec<String>
+    where
+        S: Store,
+    {
+        block_on(builder.finish().run()).unwrap();
+
+        chat.sent()
+    }
+
     #[test]
     fn manual_callback() {
         struct WelcomeBack;
@@ -67,9 +386,8 @@ mod tests {
                             chat.send_message(message.reply(format!(
                                 "Hello again, {}!",
                                 message.user().display_name().unwrap_or(&id)
-                            )));
-
-                            Ok(())
+                            )))
+                            .await
                         }
                         Ok(None) => match store.set(id, "1").await {
                             Ok(_) => Ok(()),
@@ -104,9 +422,8 @@ mod tests {
                         chat.send_message(message.reply(format!(
                             "Hello again, {}!",
                             message.user().display_name().unwrap_or(&id)
-                        )));
-
-                        Ok(())
+                        )))
+                        .await
                     }
                     Ok(None) => match store.set(id, "1").await {
                         Ok(_) => Ok(()),
@@ -123,4 +440,1062 @@ mod tests {
             .route(Route::new(r".*", true, "welcome.back", welcome_back).unwrap())
             .finish();
     }
+
+    #[test]
+    fn routes_require_directed_messages_unless_eavesdropping() {
+        let chat = TestChat::new(vec![
+            in_room("lobby", "ping", 0),
+            in_room("lobby", "Robot: ping", 7),
+            direct("ping"),
+        ]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::new(r"\A\s*ping", false, "ping", echo).unwrap());
+
+        assert_eq!(run(&chat, builder), vec!["ping", "ping"]);
+    }
+
+    #[test]
+    fn help_lists_and_searches_directed_routes() {
+        let chat = TestChat::new(vec![
+            direct("help"),
+            direct("help DEPLOY"),
+            direct("help nope"),
+        ]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(
+                Route::new(r"\Adeploy", false, "deploy", echo)
+                    .unwrap()
+                    .with_help("Deploys an app.")
+                    .with_example("deploy <app>"),
+            )
+            .route(Route::new(r"\Aecho", false, "echo", echo).unwrap())
+            .route(
+                Route::new(r".*", true, "log", |_, _: &IncomingMessage, _| -> Success {
+                    Box::pin(ok(()))
+                })
+                .unwrap()
+                .with_help("Logs everything."),
+            );
+
+        let sent = run(&chat, builder);
+
+        assert_eq!(
+            sent[0],
+            "deploy <app> - Deploys an app.\n\\Aecho\n\
+             help [term] - Lists the available commands, optionally only those matching a term."
+        );
+        assert_eq!(sent[1], "deploy <app> - Deploys an app.");
+        assert_eq!(sent[2], "No commands match \"nope\".");
+    }
+
+    #[test]
+    fn help_is_sent_privately_when_asked_in_a_room() {
+        let chat = TestChat::new(vec![in_room("general", "Robot: help", 7), direct("help")])
+            .capabilities(&[Capability::DirectMessages]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new());
+
+        run(&chat, builder);
+
+        let targets = chat.targets();
+
+        assert_eq!(targets.len(), 2);
+        assert!(targets
+            .iter()
+            .all(|target| matches!(target, Target::User(user) if user.id() == "2")));
+    }
+
+    #[test]
+    fn command_routes_parse_arguments_or_reply_with_usage() {
+        use crate::command::{Args, FromArgs, Spec, UsageError};
+
+        struct Deploy {
+            app: String,
+            env: String,
+            force: bool,
+        }
+
+        impl FromArgs for Deploy {
+            fn spec(spec: Spec) -> Spec {
+                spec.positional("app").option("env").flag("force")
+            }
+
+            fn from_args(args: Args) -> Result<Self, UsageError> {
+                Ok(Deploy {
+                    app: args.required("app")?,
+                    env: args.parse("env")?.unwrap_or_else(|| "staging".to_owned()),
+                    force: args.flag("force"),
+                })
+            }
+        }
+
+        fn deploy<C, S>(chat: Arc<C>, message: &IncomingMessage, deploy: Deploy, _: S) -> Success
+        where
+            C: ChatService,
+            S: Store,
+        {
+            let reply = format!("{} to {} (force: {})", deploy.app, deploy.env, deploy.force);
+
+            chat.send_message(message.reply(reply))
+        }
+
+        let chat = TestChat::new(vec![
+            direct("deploy 'my app' --env prod --force"),
+            direct("deploy"),
+            direct("deployment"),
+            direct("help deploy"),
+        ]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::command("deploy", deploy));
+
+        assert_eq!(
+            run(&chat, builder),
+            vec![
+                "my app to prod (force: true)",
+                "missing argument <app>\nUsage: deploy <app> [--env <env>] [--force]",
+                "deploy <app> [--env <env>] [--force]",
+            ]
+        );
+    }
+
+    #[test]
+    fn guards_filter_messages_before_callbacks() {
+        let chat = TestChat::new(vec![
+            in_room("ops", "Robot: room", 7),
+            in_room("lobby", "Robot: room", 7),
+            in_room("ops", "Robot: dm", 7),
+            direct("dm"),
+            direct("not alice"),
+        ]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(
+                Route::new(r"\Aroom", false, "room", echo)
+                    .unwrap()
+                    .with_guard(Guard::rooms(vec!["ops", "dev"])),
+            )
+            .route(
+                Route::new(r"\Adm", false, "dm", echo)
+                    .unwrap()
+                    .with_guard(Guard::direct_message()),
+            )
+            .route(
+                Route::new(r"\Anot alice", false, "not.alice", echo)
+                    .unwrap()
+                    .with_guard(!Guard::user("2").or(Guard::room("ops"))),
+            );
+
+        assert_eq!(run(&chat, builder), vec!["room", "dm"]);
+    }
+
+    #[test]
+    fn routes_requiring_a_group_are_restricted_to_its_members() {
+        let admin = User::new("1", Some("admin"), None);
+        let chat = TestChat::new(vec![
+            direct("deploy"),
+            direct("auth add 2 deployers"),
+            IncomingMessage::new(
+                Source::User(admin.clone()),
+                "auth add 2 deployers".into(),
+                0,
+            ),
+            IncomingMessage::new(Source::User(admin.clone()), "auth add 2 admins".into(), 0),
+            direct("deploy"),
+            direct("help auth"),
+            IncomingMessage::new(Source::User(admin), "auth list".into(), 0),
+        ]);
+        let config = Config {
+            admins: vec!["1".to_owned()],
+            ..Config::default()
+        };
+        let builder = Robot::build(config, chat.clone(), Memory::new()).route(
+            Route::new(r"\Adeploy", false, "deploy", echo)
+                .unwrap()
+                .with_required_group("deployers"),
+        );
+
+        assert_eq!(
+            run(&chat, builder),
+            vec![
+                "Sorry, only members of the \"deployers\" group can do that.",
+                "Sorry, only members of the \"admins\" group can do that.",
+                "Added 2 to the \"deployers\" group.",
+                "The \"admins\" group can only be changed in the robot's configuration.",
+                "deploy",
+                "No commands match \"auth\".",
+                "admins: 1\ndeployers: 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn group_changes_made_at_once_are_all_kept() {
+        let authorization = Authorization::new(&SlowStore::default(), Vec::new());
+        let (first, second) = block_on(join(
+            authorization.add("2", "deployers"),
+            authorization.add("3", "deployers"),
+        ));
+
+        assert!(first.unwrap() && second.unwrap());
+        assert_eq!(
+            block_on(authorization.members("deployers")).unwrap(),
+            vec!["2", "3"]
+        );
+    }
+
+    #[test]
+    fn priorities_stop_propagation_and_fallbacks() {
+        fn handled<C, S>(chat: Arc<C>, message: &IncomingMessage, _store: S) -> Success
+        where
+            C: ChatService,
+            S: Store,
+        {
+            message.stop_propagation();
+
+            chat.send_message(message.reply("handled"))
+        }
+
+        let chat = TestChat::new(vec![direct("stop"), direct("go"), direct("???")]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::new(r"\A(stop|go)", false, "echo", echo).unwrap())
+            .route(
+                Route::new(r"\Astop", false, "stop", handled)
+                    .unwrap()
+                    .with_priority(10),
+            )
+            .fallback(
+                Route::new(
+                    r".*",
+                    true,
+                    "unknown",
+                    |chat: Arc<Chat<TestChat>>, message: &IncomingMessage, _| {
+                        chat.send_message(message.reply("Unknown command."))
+                    },
+                )
+                .unwrap(),
+            );
+
+        assert_eq!(
+            run(&chat, builder),
+            vec!["handled", "go", "Unknown command."]
+        );
+    }
+
+    #[test]
+    fn middleware_wraps_every_route_in_registration_order() {
+        use crate::middleware::Next;
+
+        fn fail<C, S>(_chat: Arc<C>, _message: &IncomingMessage, _store: S) -> Success
+        where
+            C: ChatService,
+            S: Store,
+        {
+            Box::pin(futures::future::err(Error::custom("boom")))
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let outer_log = log.clone();
+        let inner_log = log.clone();
+        let chat = TestChat::new(vec![direct("echo hi"), direct("fail")]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::new(r"\Aecho", false, "echo", echo).unwrap())
+            .route(Route::new(r"\Afail", false, "fail", fail).unwrap())
+            .middleware(
+                move |chat: Arc<Chat<TestChat>>,
+                      message: &IncomingMessage,
+                      store,
+                      next: Next<_, _>| {
+                    let log = outer_log.clone();
+                    let namespace = next.route().namespace();
+                    let reply = message.reply("Something went wrong.");
+
+                    log.lock().unwrap().push(format!("outer {}", namespace));
+
+                    let future = next.run(chat.clone(), message, store);
+
+                    Box::pin(async move {
+                        match future.await {
+                            Ok(()) => Ok(()),
+                            Err(_) => chat.send_message(reply).await,
+                        }
+                    }) as Success
+                },
+            )
+            .middleware(
+                move |chat,
+                      message: &IncomingMessage,
+                      store,
+                      next: Next<Chat<TestChat>, Memory>| {
+                    inner_log
+                        .lock()
+                        .unwrap()
+                        .push(format!("inner {}", next.route().namespace()));
+
+                    next.run(chat, message, store)
+                },
+            );
+
+        assert_eq!(
+            run(&chat, builder),
+            vec!["echo hi", "Something went wrong."]
+        );
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["outer echo", "inner echo", "outer fail", "inner fail"]
+        );
+    }
+
+    #[test]
+    fn rate_limits_are_tracked_per_user_in_the_store() {
+        let bob = User::new("3", Some("bob"), None);
+        let chat = TestChat::new(vec![
+            direct("ping"),
+            direct("ping"),
+            IncomingMessage::new(Source::User(bob), "ping".into(), 0),
+        ]);
+        let now = Utc.with_ymd_and_hms(2020, 1, 1, 12, 0, 0).unwrap();
+        let store = SlowStore::default();
+        let builder = Robot::build(Config::default(), chat.clone(), store.clone())
+            .clock(ManualClock::new(now))
+            .route(
+                Route::new(r"\Aping", false, "ping", echo)
+                    .unwrap()
+                    .with_rate_limit(
+                        RateLimit::new(1, Duration::from_secs(3600))
+                            .per_user()
+                            .reply("Slow down!"),
+                    ),
+            );
+        let mut sent = run(&chat, builder);
+
+        sent.sort();
+        assert_eq!(sent, vec!["Slow down!", "ping", "ping"]);
+        assert!(block_on(store.get("rate_limit:ping:user:2"))
+            .unwrap()
+            .unwrap()
+            .contains(&format!("\"updated\":{}", now.timestamp_millis())));
+    }
+
+    fn hang<C, S>(_chat: Arc<C>, _message: &IncomingMessage, _store: S) -> Success
+    where
+        C: ChatService,
+        S: Store,
+    {
+        Box::pin(futures::future::pending())
+    }
+
+    #[test]
+    fn callbacks_that_exceed_their_timeout_fail() {
+        let chat = TestChat::new(vec![direct("hang")]);
+        let config = Config {
+            timeout: Some(Duration::from_secs(3600)),
+            ..Config::default()
+        };
+        let robot = Robot::build(config, chat, Memory::new())
+            .route(
+                Route::new(r"\Ahang", false, "hang", hang)
+                    .unwrap()
+                    .with_timeout(Duration::from_millis(10)),
+            )
+            .finish();
+
+        let error = block_on(robot.run()).unwrap_err();
+
+        assert!(error.is_timeout());
+        assert_eq!(error.to_string(), "timed out after 10 ms");
+    }
+
+    #[test]
+    fn in_flight_callbacks_are_cancelled_when_incoming_messages_end() {
+        fn wait<C, S>(chat: Arc<C>, message: &IncomingMessage, _store: S) -> Success
+        where
+            C: ChatService + 'static,
+            S: Store,
+        {
+            let message = message.clone();
+
+            Box::pin(async move {
+                message.cancelled().await;
+
+                chat.send_message(message.reply("cancelled")).await
+            })
+        }
+
+        let chat = TestChat::new(vec![direct("wait"), direct("echo")]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::new(r"\Await", false, "wait", wait).unwrap())
+            .route(Route::new(r"\Aecho", false, "echo", echo).unwrap());
+
+        assert_eq!(run(&chat, builder), vec!["echo", "cancelled"]);
+    }
+
+    #[test]
+    fn shutdown_handle_stops_the_robot_gracefully() {
+        fn wait<C, S>(chat: Arc<C>, message: &IncomingMessage, _store: S) -> Success
+        where
+            C: ChatService + 'static,
+            S: Store,
+        {
+            let message = message.clone();
+
+            Box::pin(async move {
+                message.cancelled().await;
+
+                chat.send_message(message.reply("finished")).await
+            })
+        }
+
+        fn hook(chat: Arc<Chat<TestChat>>, _store: Memory) -> Success {
+            chat.log.lock().unwrap().push("hook".to_owned());
+
+            Box::pin(ok(()))
+        }
+
+        let chat = TestChat::new(vec![direct("wait"), direct("hang")]).stay_open();
+        let config = Config {
+            shutdown_timeout: Some(Duration::from_millis(50)),
+            ..Config::default()
+        };
+        let robot = Robot::build(config, chat.clone(), Memory::new())
+            .route(Route::new(r"\Await", false, "wait", wait).unwrap())
+            .route(Route::new(r"\Ahang", false, "hang", hang).unwrap())
+            .on_shutdown(hook)
+            .finish();
+        let handle = robot.shutdown_handle();
+
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.shutdown();
+        });
+
+        let result = block_on(robot.run());
+
+        assert!(result.unwrap_err().is_timeout());
+        assert_eq!(chat.sent(), vec!["finished"]);
+        assert_eq!(chat.log(), vec!["hook", "adapter shutdown"]);
+    }
+
+    #[test]
+    fn event_handlers() {
+        fn welcome(chat: Arc<Chat<TestChat>>, event: &Event, _store: Memory) -> Success {
+            match event {
+                Event::UserJoined(user, room) => {
+                    let name = user.username().unwrap_or_else(|| user.id());
+                    let message = OutgoingMessage::new(
+                        Target::Room(room.clone()),
+                        format!("Welcome, {}!", name),
+                    );
+
+                    chat.send_message(message)
+                }
+                _ => Box::pin(ok(())),
+            }
+        }
+
+        let lobby = Room::new("lobby", None::<String>);
+        let chat = TestChat::new(vec![]).events(vec![
+            Event::Connected,
+            Event::UserJoined(alice(), lobby.clone()),
+            Event::UserLeft(alice(), lobby),
+        ]);
+        let builder =
+            Robot::build(Config::default(), chat.clone(), Memory::new()).on_event(welcome);
+
+        assert_eq!(run(&chat, builder), vec!["Welcome, alice!"]);
+    }
+
+    #[test]
+    fn reactions() {
+        fn acknowledge(
+            chat: Arc<Chat<TestChat>>,
+            message: &IncomingMessage,
+            _store: Memory,
+        ) -> Success {
+            match message.id() {
+                Some(id) => chat.add_reaction(id, "eyes"),
+                None => Box::pin(ok(())),
+            }
+        }
+
+        fn karma(_chat: Arc<Chat<TestChat>>, reaction: &Reaction, store: Memory) -> Success {
+            let key = reaction.message().to_string();
+
+            Box::pin(async move {
+                let count = match store.get(key.as_str()).await.unwrap() {
+                    Some(count) => count.parse::<u32>().unwrap() + 1,
+                    None => 1,
+                };
+
+                store.set(key.as_str(), count.to_string()).await.unwrap();
+
+                Ok(())
+            })
+        }
+
+        let lobby = Room::new("lobby", None::<String>);
+        let reaction =
+            |emoji: &str| Reaction::new(emoji, MessageId::new("7"), alice(), Some(lobby.clone()));
+        let store = Memory::new();
+        let chat = TestChat::new(vec![direct("deploy").with_id(MessageId::new("7"))]).events(vec![
+            Event::ReactionAdded(reaction("+1")),
+            Event::ReactionAdded(reaction("tada")),
+            Event::ReactionRemoved(reaction("+1")),
+            Event::ReactionAdded(reaction("+1")),
+        ]);
+        let builder = Robot::build(Config::default(), chat.clone(), store.clone())
+            .route(Route::new(r"\Adeploy", false, "deploy", acknowledge).unwrap())
+            .on_reaction("+1", karma);
+
+        run(&chat, builder);
+
+        assert!(chat.log().contains(&"reacted eyes to 7".to_owned()));
+        assert_eq!(block_on(store.get("7")).unwrap(), Some("2".to_owned()));
+    }
+
+    #[test]
+    fn routes_showing_typing() {
+        fn slow(chat: Arc<Chat<TestChat>>, message: &IncomingMessage, _store: Memory) -> Success {
+            let message = message.clone();
+
+            Box::pin(async move {
+                chat.log.lock().unwrap().push("working".to_owned());
+                chat.send_message(message.reply("done")).await
+            })
+        }
+
+        let chat = TestChat::new(vec![
+            in_room("ops", "Robot: slow", 7),
+            direct("slow"),
+            direct("fast"),
+        ]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(
+                Route::new(r"\Aslow", false, "slow", slow)
+                    .unwrap()
+                    .with_typing(),
+            )
+            .route(Route::new(r"\Afast", false, "fast", echo).unwrap());
+
+        // Typing indicators that fail, as they do here in direct messages, are ignored.
+        assert_eq!(run(&chat, builder), vec!["done", "done", "fast"]);
+        assert_eq!(
+            chat.log(),
+            vec![
+                "typing",
+                "working",
+                "stopped typing",
+                "working",
+                "adapter shutdown"
+            ]
+        );
+    }
+
+    #[test]
+    fn scheduled_tasks() {
+        fn standup(chat: Arc<Chat<TestChat>>, _store: Memory) -> Success {
+            let room = Room::new("engineering", None::<String>);
+
+            chat.send_message(OutgoingMessage::new(Target::Room(room), "Standup time!"))
+        }
+
+        let minute = Duration::from_secs(60);
+        let clock = ManualClock::new(Utc.with_ymd_and_hms(2020, 1, 3, 9, 29, 0).unwrap());
+        let chat = TestChat::new(vec![]).stay_open();
+        let robot = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .clock(clock.clone())
+            .schedule(Schedule::cron("30 9 * * 1-5").unwrap(), standup)
+            .schedule(
+                Schedule::every(30 * minute),
+                |chat: Arc<Chat<TestChat>>, _store| {
+                    chat.send_message(OutgoingMessage::new(Target::User(alice()), "tick"))
+                },
+            )
+            .finish();
+        let handle = robot.shutdown_handle();
+
+        let advance = async {
+            // Friday 9:30 and 9:59, then Saturday, Sunday, and Monday 9:29 and 9:30.
+            for &minutes in &[1, 29, 24 * 60, 24 * 60, 24 * 60 - 30, 1] {
+                yield_now().await;
+                clock.advance(minutes * minute);
+                yield_now().await;
+            }
+
+            handle.shutdown();
+        };
+
+        let (result, _) = block_on(join(robot.run(), advance));
+
+        result.unwrap();
+        assert_eq!(
+            chat.sent(),
+            vec![
+                "Standup time!",
+                "tick",
+                "tick",
+                "tick",
+                "tick",
+                "Standup time!"
+            ]
+        );
+    }
+
+    #[test]
+    fn reminders_survive_restarts() {
+        let clock = ManualClock::new(Utc.with_ymd_and_hms(2020, 1, 1, 12, 0, 0).unwrap());
+        let store = Memory::new();
+
+        let start = |chat: &TestChat| {
+            let robot = Robot::build(Config::default(), chat.clone(), store.clone())
+                .plugin(Reminders::new())
+                .clock(clock.clone())
+                .finish();
+            let handle = robot.shutdown_handle();
+
+            (robot, handle)
+        };
+
+        let chat = TestChat::new(vec![direct("remind me in 10 minutes to stretch")]).stay_open();
+        let (robot, handle) = start(&chat);
+        let stop = async {
+            yield_now().await;
+            handle.shutdown();
+        };
+
+        block_on(join(robot.run(), stop)).0.unwrap();
+        assert_eq!(
+            chat.sent(),
+            vec!["OK, I'll remind you at 2020-01-01 12:10 UTC."]
+        );
+
+        let chat = TestChat::new(vec![]).stay_open();
+        let (robot, handle) = start(&chat);
+        let advance = async {
+            for _ in 0..2 {
+                yield_now().await;
+                clock.advance(Duration::from_secs(5 * 60));
+                yield_now().await;
+                chat.log.lock().unwrap().push(chat.sent().len().to_string());
+            }
+
+            handle.shutdown();
+        };
+
+        block_on(join(robot.run(), advance)).0.unwrap();
+        assert_eq!(chat.sent(), vec!["Reminder: stretch"]);
+        assert_eq!(chat.log(), vec!["0", "1", "adapter shutdown"]);
+    }
+
+    #[test]
+    fn reminders_set_at_once_are_all_kept() {
+        let clock = ManualClock::new(Utc.with_ymd_and_hms(2020, 1, 1, 12, 0, 0).unwrap());
+        let chat = TestChat::new(vec![
+            direct("remind me in 1 minute to stretch"),
+            direct("remind me in 1 minute to drink water"),
+        ])
+        .stay_open();
+        let robot = Robot::build(Config::default(), chat.clone(), SlowStore::default())
+            .clock(clock.clone())
+            .plugin(Reminders::new())
+            .finish();
+        let handle = robot.shutdown_handle();
+        let advance = async {
+            Delay::new(Duration::from_millis(50)).await;
+            clock.advance(Duration::from_secs(60));
+            Delay::new(Duration::from_millis(50)).await;
+            handle.shutdown();
+        };
+
+        block_on(join(robot.run(), advance)).0.unwrap();
+
+        let mut reminders = chat.sent().split_off(2);
+
+        reminders.sort();
+        assert_eq!(
+            reminders,
+            vec!["Reminder: drink water", "Reminder: stretch"]
+        );
+    }
+
+    #[test]
+    fn reminders_are_retried_when_sending_fails() {
+        let clock = ManualClock::new(Utc.with_ymd_and_hms(2020, 1, 1, 12, 0, 0).unwrap());
+        let chat = TestChat::new(vec![
+            direct("remind #archived in 1 minute to tidy up"),
+            direct("remind me in 1 minute to stretch"),
+        ])
+        .stay_open();
+        let robot = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .clock(clock.clone())
+            .plugin(Reminders::new())
+            .finish();
+        let handle = robot.shutdown_handle();
+        let advance = async {
+            for _ in 0..2 {
+                yield_now().await;
+                clock.advance(Duration::from_secs(60));
+                yield_now().await;
+            }
+
+            handle.shutdown();
+        };
+
+        block_on(join(robot.run(), advance)).0.unwrap();
+        assert_eq!(chat.sent().last().unwrap(), "Reminder: stretch");
+        assert_eq!(
+            chat.log(),
+            vec!["send failed", "send failed", "adapter shutdown"]
+        );
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn http_routes() {
+        use std::io::{Read, Write};
+        use std::net::{TcpListener, TcpStream};
+        use std::thread;
+
+        use futures::channel::oneshot;
+
+        use super::http::{HandlerFuture, Request, Response, MAX_BODY_LENGTH};
+
+        fn notify(chat: Arc<Chat<TestChat>>, request: &Request, _store: Memory) -> HandlerFuture {
+            let room = Room::new("builds", None::<String>);
+            let body = request.text().map(|text| format!("CI: {}", text));
+
+            Box::pin(async move {
+                chat.send_message(OutgoingMessage::new(Target::Room(room), body?))
+                    .await?;
+
+                Ok(Response::new(202, "Accepted"))
+            })
+        }
+
+        fn post(address: std::net::SocketAddr, path: &str, length: usize, body: &str) -> String {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut response = String::new();
+
+            write!(
+                stream,
+                "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                path,
+                length,
+                body
+            )
+            .unwrap();
+            stream.read_to_string(&mut response).unwrap();
+
+            response.lines().next().unwrap_or_default().to_owned()
+        }
+
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = Config {
+            http_address: Some(address),
+            ..Config::default()
+        };
+        let chat = TestChat::new(vec![]).stay_open();
+        let robot = Robot::build(config, chat.clone(), Memory::new())
+            .http_route("POST", "/ci", notify)
+            .finish();
+        let handle = robot.shutdown_handle();
+        let (tx, rx) = oneshot::channel();
+
+        thread::spawn(move || {
+            // Wait for the server to start listening.
+            while TcpStream::connect(address).is_err() {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            let statuses = vec![
+                post(address, "/ci", 12, "build passed"),
+                post(address, "/nope", 0, ""),
+                post(address, "/ci", MAX_BODY_LENGTH + 1, ""),
+            ];
+
+            tx.send(statuses).unwrap();
+        });
+
+        let client = async {
+            let statuses = rx.await.unwrap();
+
+            handle.shutdown();
+
+            statuses
+        };
+
+        let (result, statuses) = block_on(join(robot.run(), client));
+
+        result.unwrap();
+        assert_eq!(
+            statuses,
+            vec![
+                "HTTP/1.1 202 Accepted",
+                "HTTP/1.1 404 Not Found",
+                "HTTP/1.1 413 Payload Too Large"
+            ]
+        );
+        assert_eq!(chat.sent(), vec!["CI: build passed"]);
+    }
+
+    #[test]
+    fn conversations() {
+        fn deploy(chat: Arc<Chat<TestChat>>, message: &IncomingMessage, _store: Memory) -> Success {
+            let message = message.clone();
+
+            Box::pin(async move {
+                chat.send_message(message.reply("Which environment?"))
+                    .await?;
+
+                let reply = match message.next_message(Duration::from_millis(50)).await {
+                    Ok(answer) => format!("Deploying to {}.", answer.body()),
+                    Err(error) if error.is_timeout() => "Never mind.".to_owned(),
+                    Err(error) => return Err(error),
+                };
+
+                chat.send_message(message.reply(reply)).await
+            })
+        }
+
+        let build = |chat: &TestChat| {
+            Robot::build(Config::default(), chat.clone(), Memory::new())
+                .route(Route::new(r"\Adeploy", false, "deploy", deploy).unwrap())
+                .route(Route::new(r"\A(prod|staging)", true, "echo", echo).unwrap())
+        };
+
+        let chat = TestChat::new(vec![
+            in_room("ops", "Robot: deploy", 7),
+            in_room("lobby", "prod", 0),
+            in_room("ops", "staging", 0),
+        ]);
+
+        assert_eq!(
+            run(&chat, build(&chat)),
+            vec!["Which environment?", "prod", "Deploying to staging."]
+        );
+
+        let chat = TestChat::new(vec![direct("deploy")]).stay_open();
+        let robot = build(&chat).finish();
+        let handle = robot.shutdown_handle();
+
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            handle.shutdown();
+        });
+
+        block_on(robot.run()).unwrap();
+
+        assert_eq!(chat.sent(), vec!["Which environment?", "Never mind."]);
+    }
+
+    #[test]
+    fn message_metadata() {
+        let sent_at = Utc.with_ymd_and_hms(2020, 1, 3, 9, 30, 0).unwrap();
+        let message = direct("deploy")
+            .with_id(MessageId::new("1578043800.000200"))
+            .with_timestamp(sent_at)
+            .with_edited()
+            .with_raw(serde_json::json!({ "type": "message", "subtype": "message_changed" }));
+
+        assert_eq!(message.id(), Some(&MessageId::new("1578043800.000200")));
+        assert_eq!(message.timestamp(), Some(sent_at));
+        assert!(message.is_edited());
+        assert_eq!(message.raw().unwrap()["subtype"], "message_changed");
+
+        let plain = direct("deploy");
+
+        assert!(plain.id().is_none() && plain.timestamp().is_none() && plain.raw().is_none());
+        assert!(!plain.is_edited());
+    }
+
+    #[test]
+    fn long_messages_are_split() {
+        fn logs(chat: Arc<Chat<TestChat>>, message: &IncomingMessage, _store: Memory) -> Success {
+            let text = RichText::new()
+                .text("Deploy failed:")
+                .code_block("error: one\nerror: two\nerror: three\n", None);
+
+            chat.send_message(message.reply_rich(text).attach(Attachment::new(
+                "deploy.log",
+                "text/plain",
+                b"...".to_vec(),
+            )))
+        }
+
+        let chat = TestChat::new(vec![direct("logs")]).max_message_length(30);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::new(r"\Alogs", false, "logs", logs).unwrap());
+
+        assert_eq!(
+            run(&chat, builder),
+            vec![
+                "Deploy failed:",
+                "```\nerror: one\nerror: two\n```",
+                "```\nerror: three\n```"
+            ]
+        );
+
+        let sent = chat.sent.lock().unwrap();
+
+        assert!(sent[..2].iter().all(|part| part.attachments().is_empty()));
+        assert_eq!(sent[2].attachments().len(), 1);
+    }
+
+    #[test]
+    fn threads() {
+        fn deploy(chat: Arc<Chat<TestChat>>, message: &IncomingMessage, _store: Memory) -> Success {
+            let message = message.clone();
+
+            Box::pin(async move {
+                chat.send_message(message.reply_in_thread("Which environment?"))
+                    .await?;
+
+                let answer = message.next_message(Duration::from_millis(50)).await?;
+                let reply = format!("Deploying to {}.", answer.body());
+
+                chat.send_message(message.reply_in_thread(reply)).await
+            })
+        }
+
+        let chat = TestChat::new(vec![
+            in_room("ops", "Robot: deploy", 7).with_thread("t1"),
+            in_room("ops", "prod", 0),
+            in_room("ops", "staging", 0).with_thread("t1"),
+        ]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::new(r"\Adeploy", false, "deploy", deploy).unwrap())
+            .route(Route::new(r"\A(prod|staging)", true, "echo", echo).unwrap());
+
+        assert_eq!(
+            run(&chat, builder),
+            vec!["Which environment?", "prod", "Deploying to staging."]
+        );
+
+        let targets = chat.targets();
+
+        match &targets[0] {
+            Target::Thread(room, thread) => assert_eq!((room.id(), thread.as_str()), ("ops", "t1")),
+            target => panic!("expected a thread, got {:?}", target),
+        }
+        assert!(matches!(&targets[1], Target::Room(_)));
+        assert!(matches!(&targets[2], Target::Thread(_, _)));
+
+        let degraded = chat.sent.lock().unwrap()[0].clone().without_thread();
+
+        assert!(matches!(degraded.target(), Target::Room(room) if room.id() == "ops"));
+        assert!(matches!(
+            direct("deploy")
+                .with_thread("t2")
+                .reply_in_thread("hi")
+                .target(),
+            Target::User(_)
+        ));
+    }
+
+    #[test]
+    fn editing_and_deleting_messages() {
+        fn deploy(chat: Arc<Chat<TestChat>>, message: &IncomingMessage, _store: Memory) -> Success {
+            let message = message.clone();
+
+            Box::pin(async move {
+                let status = chat
+                    .send_message_with_id(message.reply("Deploying..."))
+                    .await?;
+                let notice = chat
+                    .send_message_with_id(message.reply("This may take a while."))
+                    .await?;
+
+                chat.edit_message(&status, message.reply("Deployed."))
+                    .await?;
+                chat.delete_message(&notice).await
+            })
+        }
+
+        let chat = TestChat::new(vec![direct("deploy")]);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(Route::new(r"\Adeploy", false, "deploy", deploy).unwrap());
+
+        assert_eq!(
+            run(&chat, builder),
+            vec!["Deployed.", "This may take a while."]
+        );
+        assert!(chat.log().contains(&"deleted 1".to_owned()));
+    }
+
+    #[test]
+    fn routes_requiring_confirmation() {
+        let chat = TestChat::new(vec![
+            direct("drop database"),
+            direct("YES"),
+            direct("drop database"),
+            direct("no"),
+            direct("drop table"),
+            direct("yes"),
+        ]);
+        let timeout = Duration::from_secs(60);
+        let builder = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(
+                Route::new(r"\Adrop database", false, "database", echo)
+                    .unwrap()
+                    .with_confirmation(Confirmation::new(timeout)),
+            )
+            .route(
+                Route::new(r"\Adrop table", false, "table", echo)
+                    .unwrap()
+                    .with_confirmation(Confirmation::new(timeout).token()),
+            );
+        let sent = run(&chat, builder);
+        let prompt = "Are you sure? Reply \"yes\" within 60 seconds to continue.";
+
+        assert_eq!(sent.len(), 6);
+        assert_eq!(
+            sent[..4],
+            [prompt, "drop database", prompt, "OK, cancelled."]
+        );
+        assert!(sent[4].starts_with("Are you sure? Reply \""));
+        assert_ne!(sent[4], prompt);
+        assert_eq!(sent[5], "OK, cancelled.");
+    }
+
+    #[test]
+    fn shutdown_releases_conversations() {
+        let chat = TestChat::new(vec![direct("drop database")]).stay_open();
+        let robot = Robot::build(Config::default(), chat.clone(), Memory::new())
+            .route(
+                Route::new(r"\Adrop database", false, "database", echo)
+                    .unwrap()
+                    .with_confirmation(Confirmation::new(Duration::from_secs(60))),
+            )
+            .finish();
+        let handle = robot.shutdown_handle();
+        let started = Instant::now();
+
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.shutdown();
+        });
+
+        block_on(robot.run()).unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(
+            chat.sent(),
+            vec!["Are you sure? Reply \"yes\" within 60 seconds to continue."]
+        );
+    }
+
+    #[test]
+    fn attachments_fall_back_to_notices() {
+        let attachment = Attachment::new("report.csv", "text/csv", b"a,b\n".to_vec());
+        let message = direct("report")
+            .reply("Here's the report.")
+            .attach(attachment)
+            .without_attachments();
+
+        assert!(message.attachments().is_empty());
+        assert_eq!(
+            message.body(),
+            "Here's the report.\n[File not sent: report.csv (text/csv, 4 bytes)]"
+        );
+    }
 }
@@ -1,16 +1,44 @@
 //! Types for sending and receiving messages.
 
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+use futures::future::{select, Either};
+use futures_timer::Delay;
+use serde_json::Value;
+
+use crate::cancellation::{Cancellation, Cancelled};
+use crate::conversation::Conversations;
+use crate::entity::{Entity, EntityKind};
+use crate::format::{PlainText, Renderer, RichText};
+use crate::result::Error;
 use crate::room::Room;
 use crate::user::User;
 
+mod split;
+
 /// An incoming chat message.
+///
+/// Chat services create messages with `IncomingMessage::new` and add whatever else they know about
+/// them, such as their id or when they were sent, with the builder methods that follow it.
 #[derive(Clone, Debug)]
 pub struct IncomingMessage {
     body: String,
+    cancellation: Cancellation,
+    conversations: Conversations,
+    edited: bool,
+    entities: Vec<Entity>,
+    id: Option<MessageId>,
     mention_offset: usize,
+    propagation_stopped: Arc<AtomicBool>,
+    raw: Option<Arc<Value>>,
     source: Source,
+    thread: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
 }
 
 impl IncomingMessage {
@@ -18,11 +46,66 @@ impl IncomingMessage {
     pub fn new(source: Source, body: String, mention_offset: usize) -> Self {
         IncomingMessage {
             body,
+            cancellation: Cancellation::new(),
+            conversations: Conversations::new(),
+            edited: false,
+            entities: Vec::new(),
+            id: None,
             mention_offset,
-            source: source,
+            propagation_stopped: Arc::new(AtomicBool::new(false)),
+            raw: None,
+            source,
+            thread: None,
+            timestamp: None,
         }
     }
 
+    /// Sets the id the chat service gave the message, so that it can be reacted to.
+    pub fn with_id(mut self, id: MessageId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Sets when the message was sent, according to the chat service.
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Marks the message as an edit of a message that was sent earlier.
+    pub fn with_edited(mut self) -> Self {
+        self.edited = true;
+        self
+    }
+
+    /// Attaches the payload the chat service sent for the message, for plugins that need
+    /// service-specific details the robot doesn't model.
+    pub fn with_raw(mut self, raw: Value) -> Self {
+        self.raw = Some(Arc::new(raw));
+        self
+    }
+
+    /// Sets the users, rooms, and links referred to in the message's body.
+    ///
+    /// Chat services without structured entities can find them with `entity::parse`.
+    pub fn with_entities(mut self, entities: Vec<Entity>) -> Self {
+        self.entities = entities;
+        self
+    }
+
+    /// Marks the message as having been sent in a thread, identified by the chat service's id
+    /// for the thread.
+    ///
+    /// Chat services on which any message in a room can start a thread may give messages outside
+    /// of threads their own id as the thread, so that `reply_in_thread` starts a new thread.
+    pub fn with_thread<T>(mut self, thread: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.thread = Some(thread.into());
+        self
+    }
+
     /// The body of the message.
     ///
     /// If the message began with a mention of the robot, it is removed from the body of the
@@ -38,17 +121,98 @@ impl IncomingMessage {
         &self.body
     }
 
+    /// Whether or not the message was directed to the robot.
+    ///
+    /// A message is directed to the robot if it was sent privately or if it began with a mention
+    /// of the robot's name or alias.
+    pub fn is_directed(&self) -> bool {
+        self.mention_offset > 0 || self.room().is_none()
+    }
+
+    /// Signals that the message has been handled and should not be passed to any further routes.
+    ///
+    /// Clones of the message share this state, so it can be called from within a callback's
+    /// future.
+    pub fn stop_propagation(&self) {
+        self.propagation_stopped.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether or not `stop_propagation` has been called for the message.
+    pub fn is_propagation_stopped(&self) -> bool {
+        self.propagation_stopped.load(Ordering::SeqCst)
+    }
+
+    /// Whether or not the robot has begun shutting down while the message is being handled.
+    ///
+    /// Long-running callbacks should check this periodically and finish up early when it's `true`.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Returns a future that completes once the robot begins shutting down.
+    pub fn cancelled(&self) -> Cancelled {
+        self.cancellation.cancelled()
+    }
+
+    /// Associates the message with the robot's cancellation signal.
+    pub(crate) fn set_cancellation(&mut self, cancellation: Cancellation) {
+        self.cancellation = cancellation;
+    }
+
+    /// Associates the message with the robot's record of callbacks waiting for messages.
+    pub(crate) fn set_conversations(&mut self, conversations: Conversations) {
+        self.conversations = conversations;
+    }
+
+    /// Waits for the next message from the same user in the same room and thread, for at most
+    /// `timeout`.
+    ///
+    /// This lets a callback carry on a conversation, such as asking a follow-up question and
+    /// acting on the answer. The next message is passed to the waiting callback instead of the
+    /// robot's routes, whether or not it is directed to the robot. If more than one callback is
+    /// waiting on the same user and room, the one that started waiting first gets the message.
+    ///
+    /// # Errors
+    ///
+    /// Returns a timeout error if no message arrives within `timeout`, or an error as soon as the
+    /// robot starts shutting down.
+    pub fn next_message(
+        &self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<IncomingMessage, Error>> {
+        let waiting = select(self.conversations.wait(self), self.cancelled());
+
+        async move {
+            match select(waiting, Delay::new(timeout)).await {
+                Either::Left((Either::Left((Ok(message), _)), _)) => Ok(message),
+                Either::Left(_) => Err(Error::custom("the robot stopped running")),
+                Either::Right(_) => Err(Error::timeout(timeout)),
+            }
+        }
+    }
+
     /// Creates an `OutgoingMessage` targeting the source of the incoming message.
     pub fn reply<B>(&self, body: B) -> OutgoingMessage
     where
         B: Into<String>,
     {
-        let target = match self.source {
-            Source::User(ref user) => Target::User(user.clone()),
-            Source::UserInRoom(_, ref room) => Target::Room(room.clone()),
-        };
+        OutgoingMessage::new(self.reply_target(), body)
+    }
 
-        OutgoingMessage::new(target, body)
+    /// Creates an `OutgoingMessage` targeting the thread the incoming message was sent in.
+    ///
+    /// If the message wasn't sent in a thread, this is the same as `reply`.
+    pub fn reply_in_thread<B>(&self, body: B) -> OutgoingMessage
+    where
+        B: Into<String>,
+    {
+        OutgoingMessage::new(self.thread_target(), body)
+    }
+
+    /// Creates an `OutgoingMessage` with formatted text targeting the source of the incoming
+    /// message, like `reply`.
+    pub fn reply_rich(&self, text: RichText) -> OutgoingMessage {
+        OutgoingMessage::rich(self.reply_target(), text)
     }
 
     /// Creates an `OutgoingMessage` directly targeting the source of the incoming message.
@@ -70,14 +234,33 @@ impl IncomingMessage {
     where
         B: Into<String>,
     {
-        let target = match self.source {
+        OutgoingMessage::new(self.mention_target(), body)
+    }
+
+    /// The target of a reply to the message, as used by `reply`.
+    pub(crate) fn reply_target(&self) -> Target {
+        match self.source {
+            Source::User(ref user) => Target::User(user.clone()),
+            Source::UserInRoom(_, ref room) => Target::Room(room.clone()),
+        }
+    }
+
+    /// The target of a reply in the message's thread, as used by `reply_in_thread`.
+    pub(crate) fn thread_target(&self) -> Target {
+        match (self.room(), self.thread.as_ref()) {
+            (Some(room), Some(thread)) => Target::Thread(room.clone(), thread.clone()),
+            _ => self.reply_target(),
+        }
+    }
+
+    /// The target of a reply mentioning the message's sender, as used by `reply_with_mention`.
+    pub(crate) fn mention_target(&self) -> Target {
+        match self.source {
             Source::User(ref user) => Target::User(user.clone()),
             Source::UserInRoom(ref user, ref room) => {
                 Target::UserInRoom(user.clone(), room.clone())
             }
-        };
-
-        OutgoingMessage::new(target, body)
+        }
     }
 
     /// The room the message was sent from, if any.
@@ -89,12 +272,79 @@ impl IncomingMessage {
     pub fn user(&self) -> &User {
         self.source.user()
     }
+
+    /// The id the chat service gave the message, if known.
+    pub fn id(&self) -> Option<&MessageId> {
+        self.id.as_ref()
+    }
+
+    /// When the message was sent, if known.
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    /// Whether or not the message is an edit of a message that was sent earlier.
+    pub fn is_edited(&self) -> bool {
+        self.edited
+    }
+
+    /// The payload the chat service sent for the message, if the chat service provides it.
+    ///
+    /// Its structure is specific to the chat service, so plugins using it are tied to that
+    /// service.
+    pub fn raw(&self) -> Option<&Value> {
+        self.raw.as_deref()
+    }
+
+    /// The users, rooms, and links referred to in the message's body, in the order they appear.
+    ///
+    /// The range of each entity is relative to `raw_body`.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// The users mentioned in the message's body.
+    pub fn mentions(&self) -> impl Iterator<Item = &User> {
+        self.entities
+            .iter()
+            .filter_map(|entity| match entity.kind() {
+                EntityKind::Mention(user) => Some(user),
+                _ => None,
+            })
+    }
+
+    /// The rooms referred to in the message's body.
+    pub fn rooms(&self) -> impl Iterator<Item = &Room> {
+        self.entities
+            .iter()
+            .filter_map(|entity| match entity.kind() {
+                EntityKind::Room(room) => Some(room),
+                _ => None,
+            })
+    }
+
+    /// The links in the message's body.
+    pub fn urls(&self) -> impl Iterator<Item = &str> {
+        self.entities
+            .iter()
+            .filter_map(|entity| match entity.kind() {
+                EntityKind::Url(url) => Some(url.as_str()),
+                _ => None,
+            })
+    }
+
+    /// The id of the thread the message was sent in, if any.
+    pub fn thread(&self) -> Option<&str> {
+        self.thread.as_deref()
+    }
 }
 
 /// An outgoing chat message.
 #[derive(Clone, Debug)]
 pub struct OutgoingMessage {
+    attachments: Vec<Attachment>,
     body: String,
+    rich: Option<RichText>,
     target: Target,
 }
 
@@ -105,15 +355,202 @@ impl OutgoingMessage {
         B: Into<String>,
     {
         OutgoingMessage {
+            attachments: Vec::new(),
             body: body.into(),
-            target: target,
+            rich: None,
+            target,
         }
     }
 
-    /// The body of the message.
+    /// Creates a new `OutgoingMessage` with formatted text.
+    ///
+    /// The message's `body` is the text rendered as plain text, for chat services that don't
+    /// support formatting.
+    pub fn rich(target: Target, text: RichText) -> Self {
+        OutgoingMessage {
+            attachments: Vec::new(),
+            body: PlainText.render(&text),
+            rich: Some(text),
+            target,
+        }
+    }
+
+    /// The body of the message as plain text.
     pub fn body(&self) -> &str {
         &self.body
     }
+
+    /// The formatted text of the message, if it was created with formatting.
+    pub fn rich_text(&self) -> Option<&RichText> {
+        self.rich.as_ref()
+    }
+
+    /// Renders the message's body with the given renderer.
+    ///
+    /// Messages created without formatting are returned as is, without escaping.
+    pub fn render<R>(&self, renderer: &R) -> String
+    where
+        R: Renderer + ?Sized,
+    {
+        match self.rich {
+            Some(ref text) => renderer.render(text),
+            None => self.body.clone(),
+        }
+    }
+
+    /// The target of the message.
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+
+    /// Attaches a file to the message.
+    ///
+    /// Not all chat services can upload files. Check for `Capability::Attachments` in
+    /// `ChatService::capabilities` to find out whether a chat service can.
+    pub fn attach(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// The files attached to the message.
+    pub fn attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    /// Replaces the message's attachments with a notice about each one at the end of the body.
+    ///
+    /// Chat services that can't upload files use this to let the recipient know what they're
+    /// missing.
+    pub fn without_attachments(mut self) -> Self {
+        for attachment in self.attachments.drain(..) {
+            let notice = format!(
+                "[File not sent: {} ({}, {} bytes)]",
+                attachment.filename,
+                attachment.mime_type,
+                attachment.data.len()
+            );
+
+            if !self.body.is_empty() {
+                self.body.push('\n');
+            }
+
+            self.body.push_str(&notice);
+            self.rich = self
+                .rich
+                .take()
+                .map(|text| text.text(format!("\n{}", notice)));
+        }
+
+        self
+    }
+
+    /// Splits the message into as many messages as needed for each to be at most `max_length`
+    /// characters long when rendered with the given renderer.
+    ///
+    /// The robot uses this to send long messages in parts to chat services that declare a
+    /// `ChatService::max_message_length`. Bodies are split between lines where possible, then
+    /// between words, and fenced code blocks are closed and reopened around each split. The parts
+    /// are created from the rendered text, so they are sent as is rather than rendered again. Any
+    /// attachments go with the last part. A message that already fits is returned unchanged.
+    pub fn split<R>(self, renderer: &R, max_length: usize) -> Vec<OutgoingMessage>
+    where
+        R: Renderer + ?Sized,
+    {
+        let rendered = self.render(renderer);
+
+        if rendered.chars().count() <= max_length {
+            return vec![self];
+        }
+
+        let mut parts: Vec<OutgoingMessage> = split::split(&rendered, max_length)
+            .into_iter()
+            .map(|part| OutgoingMessage::new(self.target.clone(), part))
+            .collect();
+
+        if let Some(last) = parts.last_mut() {
+            last.attachments = self.attachments;
+        }
+
+        parts
+    }
+
+    /// Retargets a message for a thread to the thread's room.
+    ///
+    /// Chat services that don't support threads use this to send replies in threads to the room
+    /// instead. Messages with other targets are returned unchanged.
+    pub fn without_thread(mut self) -> Self {
+        if let Target::Thread(room, _) = self.target {
+            self.target = Target::Room(room);
+        }
+
+        self
+    }
+}
+
+/// The id a chat service gives a message, used to refer to the message later.
+///
+/// Ids are opaque to the robot. Chat services that need more than one value to find a message,
+/// such as a room and a timestamp, can combine them into a single id.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct MessageId(String);
+
+impl MessageId {
+    /// Creates a new `MessageId`.
+    pub fn new<I>(id: I) -> Self
+    where
+        I: Into<String>,
+    {
+        MessageId(id.into())
+    }
+
+    /// The id as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for MessageId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A file attached to an outgoing message.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    data: Vec<u8>,
+    filename: String,
+    mime_type: String,
+}
+
+impl Attachment {
+    /// Creates a new `Attachment` with the given file name, MIME type, and contents.
+    pub fn new<F, M>(filename: F, mime_type: M, data: Vec<u8>) -> Self
+    where
+        F: Into<String>,
+        M: Into<String>,
+    {
+        Attachment {
+            data,
+            filename: filename.into(),
+            mime_type: mime_type.into(),
+        }
+    }
+
+    /// The contents of the file.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The name of the file.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// The MIME type of the file, such as `text/csv`.
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
 }
 
 impl Display for OutgoingMessage {
@@ -150,7 +587,7 @@ impl Source {
 }
 
 /// The target of an outgoing message.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum Target {
     /// A message to a room.
     Room(Room),
@@ -158,4 +595,9 @@ pub enum Target {
     User(User),
     /// A message to a specific user in a room.
     UserInRoom(User, Room),
+    /// A message to a thread in a room, identified by the chat service's id for the thread.
+    ///
+    /// Not all chat services support threads. Those that don't should send the message to the
+    /// room instead, such as with `OutgoingMessage::without_thread`.
+    Thread(Room, String),
 }
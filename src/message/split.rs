@@ -0,0 +1,354 @@
+//! Splitting of long message bodies into parts that fit a chat service's length limit.
+
+/// Splits text into parts of at most `max_length` characters.
+///
+/// Text is split between lines where possible, then between words, and only splits words that
+/// are too long on their own. A part that ends inside a fenced code block is closed with a fence,
+/// and the block is reopened at the start of the next part, so each part renders correctly on its
+/// own. Code blocks whose fences leave no room for code within `max_length` are split like any
+/// other text instead.
+pub(crate) fn split(text: &str, max_length: usize) -> Vec<String> {
+    let max_length = max_length.max(1);
+
+    if length(text) <= max_length {
+        return vec![text.to_owned()];
+    }
+
+    let mut splitter = Splitter {
+        current: String::new(),
+        fence: None,
+        fresh: true,
+        max_length,
+        parts: Vec::new(),
+    };
+
+    for line in text.split('\n') {
+        splitter.push_line(line);
+    }
+
+    splitter.finish()
+}
+
+struct Splitter {
+    /// The part being built.
+    current: String,
+    /// The code block the current part is in, if any.
+    fence: Option<Fence>,
+    /// Whether nothing but a reopened fence has been added to the current part.
+    fresh: bool,
+    max_length: usize,
+    parts: Vec<String>,
+}
+
+impl Splitter {
+    fn push_line(&mut self, line: &str) {
+        let closes = self
+            .fence
+            .as_ref()
+            .is_some_and(|fence| fence.is_closed_by(line));
+        let fence = match self.fence {
+            Some(_) if closes => None,
+            Some(ref fence) => Some(fence.clone()),
+            None => Fence::open(line, self.max_length),
+        };
+
+        // Leave room to close the code block if the part ends inside one.
+        let reserved = fence.as_ref().map_or(0, Fence::reserved);
+
+        if !self.fits(line, true, reserved) && !self.fresh {
+            self.flush();
+
+            // Flushing already closed the code block, so there's no need to reopen it.
+            if closes && self.fence.as_ref().is_some_and(|fence| fence.reopens) {
+                self.current.clear();
+                self.fence = None;
+
+                return;
+            }
+        }
+
+        if self.fits(line, true, reserved) {
+            self.append(line, true);
+            self.fence = fence;
+
+            return;
+        }
+
+        // The line is too long for a part of its own, so break it between words.
+        let mut newline = true;
+
+        for word in line.split_inclusive(' ') {
+            self.push_word(word, newline, reserved);
+            newline = false;
+        }
+
+        self.fence = fence;
+    }
+
+    fn push_word(&mut self, word: &str, newline: bool, reserved: usize) {
+        if !self.fits(word, newline, reserved) && !self.fresh {
+            self.flush();
+        }
+
+        let mut rest = word;
+
+        // A fresh part always has room for at least one character, since code blocks are only
+        // reopened when their fences leave room for some code.
+        while !self.fits(rest, newline, reserved) {
+            let available = self
+                .max_length
+                .saturating_sub(length(&self.current) + self.separator(newline).len() + reserved)
+                .max(1);
+            let index = rest
+                .char_indices()
+                .nth(available)
+                .map_or(rest.len(), |(index, _)| index);
+
+            self.append(&rest[..index], newline);
+            self.flush();
+            rest = &rest[index..];
+        }
+
+        self.append(rest, newline);
+    }
+
+    /// Whether the text fits in the current part, leaving `reserved` characters to spare.
+    fn fits(&self, text: &str, newline: bool, reserved: usize) -> bool {
+        length(&self.current) + self.separator(newline).len() + length(text) + reserved
+            <= self.max_length
+    }
+
+    /// The separator to add before text, which starts a new line if `newline` is set.
+    fn separator(&self, newline: bool) -> &'static str {
+        // A fresh part that isn't empty holds a reopened fence, which needs a line of its own.
+        if self.current.is_empty() {
+            ""
+        } else if newline || self.fresh {
+            "\n"
+        } else {
+            ""
+        }
+    }
+
+    fn append(&mut self, text: &str, newline: bool) {
+        let separator = self.separator(newline);
+
+        self.current.push_str(separator);
+        self.current.push_str(text);
+        self.fresh = false;
+    }
+
+    /// Finishes the current part and begins the next, reopening any code block.
+    fn flush(&mut self) {
+        let mut part = self.current.trim_end().to_owned();
+
+        match self.fence {
+            Some(ref fence) if fence.reopens => {
+                let opening = fence.opening.trim_end();
+
+                // A code block that was only just opened is left for the next part instead.
+                if part == opening || part.ends_with(&format!("\n{}", opening)) {
+                    part.truncate(part.len() - opening.len());
+                    part.truncate(part.trim_end().len());
+                } else {
+                    part.push('\n');
+                    part.push_str(&fence.closing);
+                }
+
+                self.current = fence.opening.clone();
+            }
+            _ => self.current.clear(),
+        }
+
+        if !part.trim().is_empty() {
+            self.parts.push(part);
+        }
+
+        self.fresh = true;
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        if !self.fresh {
+            let part = self.current.trim_end().to_owned();
+
+            self.parts.push(part);
+        }
+
+        self.parts
+    }
+}
+
+/// A fenced code block.
+#[derive(Clone)]
+struct Fence {
+    /// The line that opened the block.
+    opening: String,
+    /// The fence that closes the block, as long as the one that opened it.
+    closing: String,
+    /// Whether the block is closed and reopened around splits, which it only is when its fences
+    /// leave room for some code.
+    reopens: bool,
+}
+
+impl Fence {
+    /// The code block opened by a line, if it's an opening fence.
+    fn open(line: &str, max_length: usize) -> Option<Self> {
+        let backticks = backticks(line);
+
+        if backticks < 3 {
+            return None;
+        }
+
+        Some(Fence {
+            opening: line.to_owned(),
+            closing: "`".repeat(backticks),
+            // Both fences, a character of code, and the newlines between them.
+            reopens: length(line) + backticks + 3 <= max_length,
+        })
+    }
+
+    /// Whether a line closes the block, which takes a fence at least as long as the opening one.
+    fn is_closed_by(&self, line: &str) -> bool {
+        backticks(line) >= self.closing.len() && line.trim().trim_start_matches('`').is_empty()
+    }
+
+    /// The characters to leave in a part for closing the block.
+    fn reserved(&self) -> usize {
+        if self.reopens {
+            self.closing.len() + 1
+        } else {
+            0
+        }
+    }
+}
+
+/// The number of backticks a line starts with, ignoring indentation.
+fn backticks(line: &str) -> usize {
+    line.trim_start().chars().take_while(|&c| c == '`').count()
+}
+
+fn length(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split;
+
+    fn check(parts: &[String], max_length: usize) {
+        for part in parts {
+            assert!(
+                part.chars().count() <= max_length,
+                "{:?} is longer than {}",
+                part,
+                max_length
+            );
+            assert!(!part.trim().is_empty(), "{:?} is empty", parts);
+        }
+    }
+
+    fn check_balanced(parts: &[String], max_length: usize) {
+        check(parts, max_length);
+
+        for part in parts {
+            let mut open = None;
+
+            for line in part.lines() {
+                let backticks = line.chars().take_while(|&c| c == '`').count();
+
+                match open {
+                    Some(length) if backticks >= length && backticks == line.len() => open = None,
+                    None if backticks >= 3 => open = Some(backticks),
+                    _ => {}
+                }
+            }
+
+            assert_eq!(open, None, "{:?} is unbalanced", part);
+        }
+    }
+
+    #[test]
+    fn leaves_short_text_alone() {
+        assert_eq!(split("hello\nworld", 11), vec!["hello\nworld"]);
+    }
+
+    #[test]
+    fn splits_between_lines_then_words() {
+        let parts = split("one two\nthree four five six\nseven", 12);
+
+        check(&parts, 12);
+        assert_eq!(parts, vec!["one two", "three four", "five six", "seven"]);
+    }
+
+    #[test]
+    fn splits_long_words() {
+        let parts = split("abcdefghij klm", 4);
+
+        check(&parts, 4);
+        assert_eq!(parts, vec!["abcd", "efgh", "ij", "klm"]);
+    }
+
+    #[test]
+    fn moves_code_blocks_that_would_be_empty() {
+        let parts = split("Output:\n```\nsome long output\n```", 24);
+
+        check_balanced(&parts, 24);
+        assert_eq!(parts, vec!["Output:", "```\nsome long output\n```"]);
+    }
+
+    #[test]
+    fn keeps_code_blocks_balanced() {
+        let text = "Logs:\n```text\nline 1\nline 2\nline 3\nline 4\n```\nDone.";
+        let parts = split(text, 25);
+
+        check_balanced(&parts, 25);
+        assert_eq!(
+            parts,
+            vec![
+                "Logs:\n```text\nline 1\n```",
+                "```text\nline 2\nline 3\n```",
+                "```text\nline 4\n```\nDone.",
+            ]
+        );
+    }
+
+    #[test]
+    fn closes_code_blocks_with_matching_fences() {
+        let text = "x\n````\na\n```\nb\nc\nd\ne\nf\ng\n````";
+        let parts = split(text, 16);
+
+        check_balanced(&parts, 16);
+        assert_eq!(
+            parts,
+            vec![
+                "x\n````\na\n````",
+                "````\n```\nb\n````",
+                "````\nc\nd\ne\n````",
+                "````\nf\ng\n````",
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_code_blocks_that_only_just_fit() {
+        let parts = split("```\nab\n```", 9);
+
+        check_balanced(&parts, 9);
+        assert_eq!(parts, vec!["```\na\n```", "```\nb\n```"]);
+    }
+
+    #[test]
+    fn splits_code_blocks_without_room_for_code_as_text() {
+        for max_length in 3..=8 {
+            let parts = split("```\nab\n```", max_length);
+
+            check(&parts, max_length);
+            assert_eq!(parts.concat().replace('\n', ""), "```ab```");
+        }
+
+        let parts = split("```rust\nlet x = 1;\n```", 10);
+
+        check(&parts, 10);
+        assert_eq!(parts, vec!["```rust", "let x = 1;", "```"]);
+    }
+}
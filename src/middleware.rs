@@ -0,0 +1,133 @@
+//! Types for adding behavior around the invocation of every route.
+
+use std::sync::Arc;
+
+use crate::{
+    callback::{Callback, CallbackFuture},
+    chat_service::ChatService,
+    message::IncomingMessage,
+    route::Route,
+    store::Store,
+};
+
+pub(crate) use self::builtin::layers;
+
+mod builtin;
+
+/// Behavior applied around the invocation of every route, such as logging, timing, or error
+/// translation.
+///
+/// Middleware is registered with `Builder::middleware` and runs in the order it was registered,
+/// the first middleware being the outermost. Each middleware decides whether, and when, to
+/// continue to the rest of the pipeline by running `next`. Not running `next` prevents the route
+/// from being invoked.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use rustin::{middleware::Next, prelude::*};
+///
+/// fn log<C, S>(chat: Arc<C>, message: &IncomingMessage, store: S, next: Next<C, S>) -> Success
+/// where
+///     C: ChatService + 'static,
+///     S: Store,
+/// {
+///     let namespace = next.route().namespace();
+///     let future = next.run(chat, message, store);
+///
+///     Box::pin(async move {
+///         let result = future.await;
+///
+///         if let Err(ref error) = result {
+///             eprintln!("{} failed: {}", namespace, error);
+///         }
+///
+///         result
+///     })
+/// }
+/// ```
+pub trait Middleware<C, S>
+where
+    C: ChatService,
+    S: Store,
+{
+    /// Handles a message on its way to a route, running `next` to continue.
+    fn call(
+        &self,
+        chat: Arc<C>,
+        message: &IncomingMessage,
+        store: S,
+        next: Next<C, S>,
+    ) -> CallbackFuture;
+}
+
+impl<F, C, S> Middleware<C, S> for F
+where
+    F: Fn(Arc<C>, &IncomingMessage, S, Next<C, S>) -> CallbackFuture,
+    C: ChatService,
+    S: Store,
+{
+    fn call(
+        &self,
+        chat: Arc<C>,
+        message: &IncomingMessage,
+        store: S,
+        next: Next<C, S>,
+    ) -> CallbackFuture {
+        self(chat, message, store, next)
+    }
+}
+
+/// The remainder of the middleware pipeline for a route.
+pub struct Next<C, S>
+where
+    C: ChatService,
+    S: Store,
+{
+    index: usize,
+    middleware: Arc<Vec<Box<dyn Middleware<C, S>>>>,
+    route: Arc<Route<C, S>>,
+}
+
+impl<C, S> Next<C, S>
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    pub(crate) fn new(
+        middleware: Arc<Vec<Box<dyn Middleware<C, S>>>>,
+        route: Arc<Route<C, S>>,
+    ) -> Self {
+        Next {
+            index: 0,
+            middleware,
+            route,
+        }
+    }
+
+    /// The route the message is being passed to.
+    pub fn route(&self) -> &Route<C, S> {
+        &self.route
+    }
+
+    /// Runs the next middleware in the pipeline or, if there are none left, the route itself.
+    ///
+    /// The robot's own middleware runs after any added with `Builder::middleware`, enforcing the
+    /// route's options. If the route requires an authorization group the sender isn't a member
+    /// of, the robot replies with a denial instead of invoking the route. If the route has reached
+    /// its rate limit, the message is ignored, optionally with a reply. If the route requires
+    /// confirmation, the robot asks for it and only continues once it's given. If the route shows
+    /// typing, the indicator is shown while the callback runs. If the route's callback doesn't
+    /// finish within the route's timeout, it is dropped and a timeout error is returned.
+    pub fn run(mut self, chat: Arc<C>, message: &IncomingMessage, store: S) -> CallbackFuture {
+        if let Some(middleware) = self.middleware.clone().get(self.index) {
+            self.index += 1;
+
+            return middleware.call(chat, message, store, self);
+        }
+
+        self.route.call(chat, message, store)
+    }
+}
@@ -0,0 +1,224 @@
+//! The middleware the robot applies to every route, after any added with `Builder::middleware`.
+//!
+//! Each layer enforces one of the options set on a route, passing messages for routes without the
+//! option straight through to the rest of the pipeline.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{select, Either};
+use futures_timer::Delay;
+
+use super::{Middleware, Next};
+use crate::{
+    authorization::Authorization,
+    callback::CallbackFuture,
+    chat_service::ChatService,
+    message::IncomingMessage,
+    result::Error,
+    schedule::SharedClock,
+    store::Store,
+};
+
+/// Creates the built-in layers in the order they run.
+///
+/// `timeout` is the default from `Config::timeout`, for routes that don't set their own.
+pub(crate) fn layers<C, S>(
+    authorization: Authorization<S>,
+    clock: SharedClock,
+    timeout: Option<Duration>,
+) -> Vec<Box<dyn Middleware<C, S>>>
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    vec![
+        Box::new(RequireGroup { authorization }),
+        Box::new(LimitRate { clock }),
+        Box::new(Confirm),
+        Box::new(ShowTyping),
+        Box::new(Timeout { default: timeout }),
+    ]
+}
+
+/// Replies with a denial instead of invoking a route when the sender isn't a member of the
+/// route's required authorization group.
+struct RequireGroup<S>
+where
+    S: Store,
+{
+    authorization: Authorization<S>,
+}
+
+impl<C, S> Middleware<C, S> for RequireGroup<S>
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(
+        &self,
+        chat: Arc<C>,
+        message: &IncomingMessage,
+        store: S,
+        next: Next<C, S>,
+    ) -> CallbackFuture {
+        let group = match next.route().required_group() {
+            Some(group) => group.to_owned(),
+            None => return next.run(chat, message, store),
+        };
+        let authorization = self.authorization.clone();
+        let message = message.clone();
+
+        Box::pin(async move {
+            if authorization.is_member(message.user().id(), &group).await? {
+                return next.run(chat, &message, store).await;
+            }
+
+            let reply = message.reply(format!(
+                "Sorry, only members of the \"{}\" group can do that.",
+                group
+            ));
+
+            chat.send_message(reply).await
+        })
+    }
+}
+
+/// Ignores messages for a route that has reached its rate limit, optionally with a reply.
+struct LimitRate {
+    clock: SharedClock,
+}
+
+impl<C, S> Middleware<C, S> for LimitRate
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(
+        &self,
+        chat: Arc<C>,
+        message: &IncomingMessage,
+        store: S,
+        next: Next<C, S>,
+    ) -> CallbackFuture {
+        let rate_limit = match next.route().rate_limit() {
+            Some(rate_limit) => rate_limit.clone(),
+            None => return next.run(chat, message, store),
+        };
+        let namespace = next.route().namespace();
+        let clock = self.clock.clone();
+        let message = message.clone();
+
+        Box::pin(async move {
+            if rate_limit
+                .acquire(&store, &clock, namespace, &message)
+                .await?
+            {
+                return next.run(chat, &message, store).await;
+            }
+
+            match rate_limit.reply_text() {
+                Some(reply) => chat.send_message(message.reply(reply)).await,
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+/// Asks the user to confirm a route's invocation, only continuing once they do.
+struct Confirm;
+
+impl<C, S> Middleware<C, S> for Confirm
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(
+        &self,
+        chat: Arc<C>,
+        message: &IncomingMessage,
+        store: S,
+        next: Next<C, S>,
+    ) -> CallbackFuture {
+        let confirmation = match next.route().confirmation() {
+            Some(confirmation) => confirmation.clone(),
+            None => return next.run(chat, message, store),
+        };
+        let message = message.clone();
+
+        Box::pin(async move {
+            if confirmation.confirm(&*chat, &message).await? {
+                next.run(chat, &message, store).await
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+/// Shows that the robot is typing while the rest of the pipeline runs, for routes that ask for it.
+///
+/// Typing indicators are cosmetic, so errors starting or stopping them are ignored rather than
+/// failing the route.
+struct ShowTyping;
+
+impl<C, S> Middleware<C, S> for ShowTyping
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(
+        &self,
+        chat: Arc<C>,
+        message: &IncomingMessage,
+        store: S,
+        next: Next<C, S>,
+    ) -> CallbackFuture {
+        let typing = match next.route().typing() {
+            Some(typing) => *typing,
+            None => return next.run(chat, message, store),
+        };
+        let target = message.thread_target();
+        let message = message.clone();
+
+        Box::pin(async move {
+            let _ = typing.start(&*chat, &target).await;
+            let result = next.run(chat.clone(), &message, store).await;
+            let _ = typing.stop(&*chat, &target).await;
+
+            result
+        })
+    }
+}
+
+/// Drops a route's callback and fails with a timeout error if it runs for too long.
+struct Timeout {
+    default: Option<Duration>,
+}
+
+impl<C, S> Middleware<C, S> for Timeout
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(
+        &self,
+        chat: Arc<C>,
+        message: &IncomingMessage,
+        store: S,
+        next: Next<C, S>,
+    ) -> CallbackFuture {
+        let duration = match next.route().timeout().or(self.default) {
+            Some(duration) => duration,
+            None => return next.run(chat, message, store),
+        };
+        let future = next.run(chat, message, store);
+
+        Box::pin(async move {
+            match select(future, Delay::new(duration)).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Err(Error::timeout(duration)),
+            }
+        })
+    }
+}
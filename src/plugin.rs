@@ -0,0 +1,16 @@
+//! Types for packaging reusable behavior for a robot.
+
+use crate::{chat_service::ChatService, robot::Builder, store::Store};
+
+/// A bundle of routes, scheduled tasks, and other behavior that can be added to a robot in one
+/// step with `Builder::plugin`.
+///
+/// Rustin comes with a plugin for reminders in the `reminders` module.
+pub trait Plugin<C, S>
+where
+    C: ChatService,
+    S: Store,
+{
+    /// Adds the plugin's behavior to the robot being built.
+    fn register(self, builder: Builder<C, S>) -> Builder<C, S>;
+}
@@ -7,6 +7,6 @@ pub use crate::{
     message::IncomingMessage,
     result::{Error, Success},
     robot::Robot,
-    route::Route,
+    route::{Confirmation, Guard, RateLimit, Route},
     store::Store,
 };
@@ -0,0 +1,77 @@
+//! Types for emoji reactions to messages.
+
+use std::sync::Arc;
+
+use crate::{callback::CallbackFuture, message::MessageId, room::Room, store::Store, user::User};
+
+/// An emoji reaction a user added to or removed from a message.
+#[derive(Clone, Debug)]
+pub struct Reaction {
+    emoji: String,
+    message: MessageId,
+    room: Option<Room>,
+    user: User,
+}
+
+impl Reaction {
+    /// Creates a new `Reaction`.
+    ///
+    /// The emoji is identified by its name without colons, such as `+1` or `eyes`. Chat services
+    /// that identify emoji some other way should translate them to names.
+    pub fn new<E>(emoji: E, message: MessageId, user: User, room: Option<Room>) -> Self
+    where
+        E: Into<String>,
+    {
+        Reaction {
+            emoji: emoji.into(),
+            message,
+            room,
+            user,
+        }
+    }
+
+    /// The name of the emoji, such as `+1`.
+    pub fn emoji(&self) -> &str {
+        &self.emoji
+    }
+
+    /// The id of the message that was reacted to.
+    pub fn message(&self) -> &MessageId {
+        &self.message
+    }
+
+    /// The room the message that was reacted to is in, if any.
+    pub fn room(&self) -> Option<&Room> {
+        self.room.as_ref()
+    }
+
+    /// The user that reacted.
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+}
+
+/// A handler that reacts to users adding a particular emoji reaction to messages.
+///
+/// Reaction handlers are registered with `Builder::on_reaction`. To handle every reaction, or
+/// reactions being removed, use an `EventHandler` instead.
+pub trait ReactionHandler<C, S> {
+    /// Invokes the handler with the reaction that was added.
+    fn call(&self, chat: Arc<C>, reaction: &Reaction, store: S) -> CallbackFuture;
+}
+
+impl<F, C, S> ReactionHandler<C, S> for F
+where
+    F: Fn(Arc<C>, &Reaction, S) -> CallbackFuture,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, reaction: &Reaction, store: S) -> CallbackFuture {
+        self(chat, reaction, store)
+    }
+}
+
+/// A reaction handler paired with the emoji it handles.
+pub(crate) struct ReactionRoute<C, S> {
+    pub(crate) emoji: String,
+    pub(crate) handler: Box<dyn ReactionHandler<C, S>>,
+}
@@ -0,0 +1,252 @@
+//! A plugin that lets users schedule reminders from chat.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::lock::Mutex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    callback::{Callback, CallbackFuture, Task},
+    chat_service::ChatService,
+    message::{IncomingMessage, OutgoingMessage, Target},
+    plugin::Plugin,
+    result::Error,
+    robot::Builder,
+    room::Room,
+    route::Route,
+    schedule::{Clock, Schedule, SharedClock},
+    store::{ScopedStore, Store},
+};
+
+mod parse;
+
+const DESCRIPTION: &str = "Sends a reminder to you or a room at a later time.";
+const PATTERN: &str =
+    r"(?i)\A\s*remind\s+(?P<who>me|#\S+)\s+(?P<when>.+?)\s+to\s+(?P<what>.+?)\s*\z";
+const KEY: &str = "pending";
+const SCOPE: &str = "reminders";
+
+/// A plugin that lets users schedule reminders from chat, such as:
+///
+/// * `remind me in 10 minutes to check the build`
+/// * `remind #ops at 9am tomorrow to rotate the logs`
+///
+/// Times are interpreted in UTC, according to the clock set with `Builder::clock`. Reminders are
+/// persisted in the robot's `Store`, so they survive restarts, and are delivered to the user or
+/// room they were set for by a task that checks for due reminders every `interval` while the robot
+/// is running. Reminders that fail to send are kept and tried again at the next check.
+///
+/// # Examples
+///
+/// ```
+/// use rustin::{chat_service::Shell, prelude::*, reminders::Reminders, store::Memory};
+///
+/// let robot = Robot::build(Config::default(), Shell::default(), Memory::new())
+///     .plugin(Reminders::new())
+///     .finish();
+/// ```
+pub struct Reminders {
+    interval: Duration,
+}
+
+impl Reminders {
+    /// Creates a new `Reminders` plugin that checks for due reminders every second.
+    pub fn new() -> Self {
+        Reminders {
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets how often to check for due reminders.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+impl Default for Reminders {
+    fn default() -> Self {
+        Reminders::new()
+    }
+}
+
+impl<C, S> Plugin<C, S> for Reminders
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn register(self, builder: Builder<C, S>) -> Builder<C, S> {
+        let clock = builder.shared_clock();
+        let lock = Arc::new(Mutex::new(()));
+        let remind = Remind {
+            clock: clock.clone(),
+            lock: lock.clone(),
+            pattern: Regex::new(PATTERN).expect("creating reminder regex"),
+        };
+
+        let route = Route::new(PATTERN, false, "reminders", remind)
+            .expect("creating reminder route")
+            .with_help(DESCRIPTION)
+            .with_example("remind me in <amount> <unit> to <message>")
+            .with_example("remind #<room> at <time> [today|tomorrow] to <message>");
+
+        builder
+            .route(route)
+            .schedule(Schedule::every(self.interval), Deliver { clock, lock })
+    }
+}
+
+/// A reminder waiting to be delivered.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Reminder {
+    body: String,
+    /// When the reminder is due, in milliseconds since the Unix epoch.
+    due: i64,
+    target: Target,
+}
+
+/// The callback for the `remind` command.
+struct Remind {
+    clock: SharedClock,
+    /// Held while changing the pending reminders, shared with `Deliver`.
+    lock: Arc<Mutex<()>>,
+    pattern: Regex,
+}
+
+impl<C, S> Callback<C, S> for Remind
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, message: &IncomingMessage, store: S) -> CallbackFuture {
+        let captures = match self.pattern.captures(message.body()) {
+            Some(captures) => captures,
+            None => return Box::pin(async { Ok(()) }),
+        };
+
+        let who = captures["who"].to_owned();
+        let when = captures["when"].to_owned();
+        let body = format!("Reminder: {}", &captures["what"]);
+        let due = parse::parse(&when, self.clock.now());
+        let lock = self.lock.clone();
+        let message = message.clone();
+
+        Box::pin(async move {
+            let due = match due {
+                Some(due) => due,
+                None => {
+                    let reply = format!("Sorry, I don't know when \"{}\" is.", when);
+
+                    return chat.send_message(message.reply(reply)).await;
+                }
+            };
+
+            let (target, recipient) = if who.eq_ignore_ascii_case("me") {
+                (message.mention_target(), "you".to_owned())
+            } else {
+                (Target::Room(room(&message, &who[1..])), who)
+            };
+
+            let store = store.scoped(SCOPE);
+            let guard = lock.lock().await;
+            let mut reminders = load(&store).await?;
+
+            reminders.push(Reminder {
+                body,
+                due: due.timestamp_millis(),
+                target,
+            });
+
+            save(&store, &reminders).await?;
+            drop(guard);
+
+            let reply = format!(
+                "OK, I'll remind {} at {}.",
+                recipient,
+                due.format("%Y-%m-%d %H:%M UTC")
+            );
+
+            chat.send_message(message.reply(reply)).await
+        })
+    }
+}
+
+/// The scheduled task that delivers due reminders.
+struct Deliver {
+    clock: SharedClock,
+    /// Held while changing the pending reminders, shared with `Remind`.
+    lock: Arc<Mutex<()>>,
+}
+
+impl<C, S> Task<C, S> for Deliver
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    fn call(&self, chat: Arc<C>, store: S) -> CallbackFuture {
+        let now = self.clock.now().timestamp_millis();
+        let lock = self.lock.clone();
+
+        Box::pin(async move {
+            let store = store.scoped(SCOPE);
+            let guard = lock.lock().await;
+            let (due, pending): (Vec<Reminder>, Vec<Reminder>) = load(&store)
+                .await?
+                .into_iter()
+                .partition(|reminder| reminder.due <= now);
+
+            if due.is_empty() {
+                return Ok(());
+            }
+
+            // Save first so reminders that are delivered aren't sent again, then put back any that
+            // fail to send so they're tried again at the next check.
+            save(&store, &pending).await?;
+            drop(guard);
+
+            let mut failed = Vec::new();
+
+            for reminder in due {
+                let message = OutgoingMessage::new(reminder.target.clone(), reminder.body.clone());
+
+                if chat.send_message(message).await.is_err() {
+                    failed.push(reminder);
+                }
+            }
+
+            if failed.is_empty() {
+                return Ok(());
+            }
+
+            let _guard = lock.lock().await;
+            let mut pending = load(&store).await?;
+
+            pending.extend(failed);
+            save(&store, &pending).await
+        })
+    }
+}
+
+/// The room named in a reminder, which is the message's own room if the name matches it.
+fn room(message: &IncomingMessage, name: &str) -> Room {
+    match message.room() {
+        Some(room) if room.id() == name || room.name() == Some(name) => room.clone(),
+        _ => Room::new(name, Some(name)),
+    }
+}
+
+async fn load<S>(store: &ScopedStore<S>) -> Result<Vec<Reminder>, Error>
+where
+    S: Store,
+{
+    Ok(store.get_json(KEY).await?.unwrap_or_default())
+}
+
+async fn save<S>(store: &ScopedStore<S>, reminders: &[Reminder]) -> Result<(), Error>
+where
+    S: Store,
+{
+    store.set_json(KEY, reminders).await
+}
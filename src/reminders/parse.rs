@@ -0,0 +1,130 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use regex::{Captures, Regex};
+
+/// Determines when a time expression like "in 10 minutes" or "at 9am tomorrow" refers to,
+/// relative to `now`.
+///
+/// Expressions are either a relative amount of time, or a time of day optionally preceded or
+/// followed by "today" or "tomorrow". A time of day without a day refers to its next occurrence.
+pub(super) fn parse(expression: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    static RELATIVE: OnceLock<Regex> = OnceLock::new();
+    static ABSOLUTE: OnceLock<Regex> = OnceLock::new();
+
+    let relative = RELATIVE.get_or_init(|| {
+        Regex::new(
+            r"(?i)\Ain\s+(?P<amount>an?|\d+)\s*(?P<unit>s|secs?|seconds?|m|mins?|minutes?|h|hrs?|hours?|d|days?|w|weeks?)\z",
+        )
+        .expect("creating relative time regex")
+    });
+    let absolute = ABSOLUTE.get_or_init(|| {
+        Regex::new(
+            r"(?i)\A(?:(?P<before>today|tomorrow)\s+)?at\s+(?P<hour>\d{1,2})(?::(?P<minute>\d{2}))?\s*(?P<meridiem>am|pm)?(?:\s+(?P<after>today|tomorrow))?\z",
+        )
+        .expect("creating absolute time regex")
+    });
+
+    let expression = expression.trim();
+
+    if let Some(captures) = relative.captures(expression) {
+        return parse_relative(&captures, now);
+    }
+
+    absolute
+        .captures(expression)
+        .and_then(|captures| parse_absolute(&captures, now))
+}
+
+fn parse_relative(captures: &Captures<'_>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let amount = match &captures["amount"] {
+        amount if amount.eq_ignore_ascii_case("a") || amount.eq_ignore_ascii_case("an") => 1,
+        amount => amount.parse().ok()?,
+    };
+
+    let duration = match captures["unit"].to_lowercase().chars().next()? {
+        's' => Duration::try_seconds(amount)?,
+        'm' => Duration::try_minutes(amount)?,
+        'h' => Duration::try_hours(amount)?,
+        'd' => Duration::try_days(amount)?,
+        _ => Duration::try_weeks(amount)?,
+    };
+
+    now.checked_add_signed(duration)
+}
+
+fn parse_absolute(captures: &Captures<'_>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let mut hour: u32 = captures["hour"].parse().ok()?;
+    let minute = match captures.name("minute") {
+        Some(minute) => minute.as_str().parse().ok()?,
+        None => 0,
+    };
+
+    if let Some(meridiem) = captures.name("meridiem") {
+        if hour == 0 || hour > 12 {
+            return None;
+        }
+
+        hour %= 12;
+
+        if meridiem.as_str().eq_ignore_ascii_case("pm") {
+            hour += 12;
+        }
+    }
+
+    let time = NaiveTime::from_hms_opt(hour, minute, 0)?;
+    let day = captures
+        .name("before")
+        .or_else(|| captures.name("after"))
+        .map(|day| day.as_str().to_lowercase());
+    let today = now.date_naive().and_time(time).and_utc();
+
+    match day.as_deref() {
+        Some("tomorrow") => today.checked_add_signed(Duration::days(1)),
+        Some(_) if today <= now => None,
+        Some(_) => Some(today),
+        None if today <= now => today.checked_add_signed(Duration::days(1)),
+        None => Some(today),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::parse;
+
+    fn at(day: u32, hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2020, 1, day, hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_relative_times() {
+        let now = at(1, 12, 0, 0);
+
+        assert_eq!(parse("in 10 minutes", now), Some(at(1, 12, 10, 0)));
+        assert_eq!(parse("in 1 min", now), Some(at(1, 12, 1, 0)));
+        assert_eq!(parse("in 30s", now), Some(at(1, 12, 0, 30)));
+        assert_eq!(parse("in an hour", now), Some(at(1, 13, 0, 0)));
+        assert_eq!(parse("IN 2 Days", now), Some(at(3, 12, 0, 0)));
+        assert_eq!(parse("in 1 week", now), Some(at(8, 12, 0, 0)));
+        assert_eq!(parse("in a while", now), None);
+    }
+
+    #[test]
+    fn parses_times_of_day() {
+        let now = at(1, 12, 0, 0);
+
+        assert_eq!(parse("at 9am tomorrow", now), Some(at(2, 9, 0, 0)));
+        assert_eq!(parse("tomorrow at 9:30 am", now), Some(at(2, 9, 30, 0)));
+        assert_eq!(parse("at 9am", now), Some(at(2, 9, 0, 0)));
+        assert_eq!(parse("at 3pm", now), Some(at(1, 15, 0, 0)));
+        assert_eq!(parse("at 12am", now), Some(at(2, 0, 0, 0)));
+        assert_eq!(parse("at 12pm", now), Some(at(2, 12, 0, 0)));
+        assert_eq!(parse("at 17:45 today", now), Some(at(1, 17, 45, 0)));
+        assert_eq!(parse("at 9am today", now), None);
+        assert_eq!(parse("at 13pm", now), None);
+        assert_eq!(parse("at 24:00", now), None);
+    }
+}
@@ -5,6 +5,7 @@ use std::future::Future;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::pin::Pin;
+use std::time::Duration;
 
 /// A type indicating a successful operation  that has no meaningful return value.
 ///
@@ -27,6 +28,18 @@ impl Error {
             inner: ErrorKind::Custom(message.into()),
         }
     }
+
+    /// Create an error indicating that an operation did not finish within the given duration.
+    pub fn timeout(duration: Duration) -> Self {
+        Error {
+            inner: ErrorKind::Timeout(duration),
+        }
+    }
+
+    /// Whether or not the error was caused by an operation taking too long.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self.inner, ErrorKind::Timeout(_))
+    }
 }
 
 impl Display for Error {
@@ -49,6 +62,12 @@ impl<'a> From<&'a str> for Error {
     }
 }
 
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::custom(error.to_string())
+    }
+}
+
 impl From<regex::Error> for Error {
     fn from(error: regex::Error) -> Self {
         Error {
@@ -62,10 +81,17 @@ enum ErrorKind {
     Custom(String),
     // Io(std::io::Error),
     Regex(regex::Error),
+    Timeout(Duration),
 }
 
 impl Display for ErrorKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(f, "{}", self)
+        match *self {
+            ErrorKind::Custom(ref message) => write!(f, "{}", message),
+            ErrorKind::Regex(ref error) => write!(f, "{}", error),
+            ErrorKind::Timeout(duration) => {
+                write!(f, "timed out after {} ms", duration.as_millis())
+            }
+        }
     }
 }
@@ -1,15 +1,33 @@
 //! The `Robot` type, which drives Rustin's runtime.
 
+use std::cmp::Reverse;
 use std::sync::Arc;
 
-use futures::stream::StreamExt;
+use futures::{
+    future::{select, Either, FutureExt},
+    select_biased,
+    stream::{FuturesUnordered, StreamExt},
+};
+use futures_timer::Delay;
 
+#[cfg(feature = "http")]
+use crate::http::{self, Endpoint, Handler};
 use crate::{
-    callback::Callback,
-    chat_service::ChatService,
+    authorization::{self, Authorization},
+    callback::Task,
+    cancellation::Cancellation,
+    chat_service::{Chat, ChatService},
     config::Config,
+    conversation::Conversations,
+    event::{Event, EventHandler},
+    help,
+    message::IncomingMessage,
+    middleware::{self, Middleware, Next},
+    plugin::Plugin,
+    reaction::{ReactionHandler, ReactionRoute},
     result::Error,
     route::Route,
+    schedule::{Clock, Job, Schedule, SharedClock},
     store::Store,
 };
 
@@ -20,14 +38,23 @@ where
     S: Store,
 {
     chat_service: C,
+    clock: SharedClock,
     config: Config,
+    #[cfg(feature = "http")]
+    endpoints: Vec<Endpoint<C, S>>,
+    event_handlers: Vec<Box<dyn EventHandler<C, S>>>,
+    fallbacks: Vec<Route<C, S>>,
+    jobs: Vec<Job<C, S>>,
+    middleware: Vec<Box<dyn Middleware<C, S>>>,
+    reaction_routes: Vec<ReactionRoute<C, S>>,
     routes: Vec<Route<C, S>>,
+    shutdown_hooks: Vec<Box<dyn Task<C, S>>>,
     store: S,
 }
 
 impl<C, S> Builder<C, S>
 where
-    C: ChatService,
+    C: ChatService + 'static,
     S: Store,
 {
     /// Adds a route.
@@ -37,12 +64,150 @@ where {
         self
     }
 
+    /// Adds a fallback route, which is only tried for messages that no other route matches.
+    ///
+    /// Fallback routes are tried in order of priority like any other route, and are not listed by
+    /// the `help` command.
+    pub fn fallback(mut self, route: Route<C, S>) -> Self {
+        self.fallbacks.push(route);
+        self
+    }
+
+    /// Adds middleware to run around the invocation of every route.
+    ///
+    /// Middleware runs in the order it was added, the first being the outermost.
+    pub fn middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware<C, S> + 'static,
+    {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Adds the routes, scheduled tasks, and other behavior of a plugin.
+    pub fn plugin<P>(self, plugin: P) -> Self
+    where
+        P: Plugin<C, S>,
+    {
+        plugin.register(self)
+    }
+
+    /// Adds a handler for events from the chat service other than messages.
+    pub fn on_event<H>(mut self, handler: H) -> Self
+    where
+        H: EventHandler<C, S> + 'static,
+    {
+        self.event_handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Adds a handler for users adding a reaction with the given emoji to a message, such as
+    /// `+1`.
+    ///
+    /// Reaction handlers run after the event handlers for the reaction, in the order they were
+    /// added.
+    pub fn on_reaction<H>(mut self, emoji: &str, handler: H) -> Self
+    where
+        H: ReactionHandler<C, S> + 'static,
+    {
+        self.reaction_routes.push(ReactionRoute {
+            emoji: emoji.to_owned(),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    /// Adds a route for the HTTP server, handling requests with the given method and path.
+    ///
+    /// Only available with the `http` feature. The server listens on `Config::http_address`.
+    #[cfg(feature = "http")]
+    pub fn http_route<H>(mut self, method: &str, path: &str, handler: H) -> Self
+    where
+        H: Handler<C, S> + 'static,
+    {
+        self.endpoints.push(Endpoint {
+            handler: Box::new(handler),
+            method: method.to_owned(),
+            path: path.to_owned(),
+        });
+        self
+    }
+
+    /// Adds a task to run on a schedule while the robot is running.
+    ///
+    /// Scheduled tasks can send messages to any `Target` with the chat service they receive. An
+    /// error from a scheduled task stops the robot, just like an error from a route.
+    pub fn schedule<T>(mut self, schedule: Schedule, task: T) -> Self
+    where
+        T: Task<C, S> + 'static,
+    {
+        self.jobs.push(Job {
+            schedule,
+            task: Box::new(task),
+        });
+        self
+    }
+
+    /// Sets the clock used to run scheduled tasks, which is `SystemClock` by default.
+    pub fn clock<K>(self, clock: K) -> Self
+    where
+        K: Clock + 'static,
+    {
+        self.clock.set(clock);
+        self
+    }
+
+    /// The robot's clock, for plugins that need the current time.
+    ///
+    /// The returned clock follows any clock set with `Builder::clock`, whether before or after
+    /// this is called.
+    pub fn shared_clock(&self) -> SharedClock {
+        self.clock.clone()
+    }
+
+    /// Adds a task to run when the robot shuts down, after in-flight callbacks have finished.
+    ///
+    /// Shutdown hooks run in the order they were added.
+    pub fn on_shutdown<T>(mut self, hook: T) -> Self
+    where
+        T: Task<C, S> + 'static,
+    {
+        self.shutdown_hooks.push(Box::new(hook));
+        self
+    }
+
     /// Creates a `Robot` from the builder.
-    pub fn finish(self) -> Robot<C, S> {
+    ///
+    /// Routes for the built-in `auth` and `help` commands are added after all other routes.
+    pub fn finish(mut self) -> Robot<C, S> {
+        let authorization = Authorization::new(&self.store, self.config.admins.clone());
+
+        self.routes.extend(authorization::routes(&authorization));
+
+        let help = help::route(&self.routes, &authorization);
+        self.routes.push(help);
+        self.middleware.extend(middleware::layers(
+            authorization,
+            self.clock.clone(),
+            self.config.timeout,
+        ));
+
         Robot {
+            cancellation: Cancellation::new(),
             chat_service: Arc::new(self.chat_service),
+            clock: self.clock,
             config: self.config,
-            routes: self.routes,
+            conversations: Conversations::new(),
+            #[cfg(feature = "http")]
+            endpoints: self.endpoints,
+            event_handlers: self.event_handlers,
+            fallbacks: prioritize(self.fallbacks),
+            jobs: self.jobs,
+            middleware: Arc::new(self.middleware),
+            reaction_routes: self.reaction_routes,
+            routes: prioritize(self.routes),
+            shutdown: Cancellation::new(),
+            shutdown_hooks: self.shutdown_hooks,
             store: self.store,
         }
     }
@@ -54,37 +219,294 @@ where
     C: ChatService,
     S: Store,
 {
+    cancellation: Cancellation,
     chat_service: Arc<C>,
+    clock: SharedClock,
     config: Config,
-    routes: Vec<Route<C, S>>,
+    conversations: Conversations,
+    #[cfg(feature = "http")]
+    endpoints: Vec<Endpoint<C, S>>,
+    event_handlers: Vec<Box<dyn EventHandler<C, S>>>,
+    fallbacks: Vec<Arc<Route<C, S>>>,
+    jobs: Vec<Job<C, S>>,
+    middleware: Arc<Vec<Box<dyn Middleware<C, S>>>>,
+    reaction_routes: Vec<ReactionRoute<C, S>>,
+    routes: Vec<Arc<Route<C, S>>>,
+    shutdown: Cancellation,
+    shutdown_hooks: Vec<Box<dyn Task<C, S>>>,
     store: S,
 }
 
-impl<C, S> Robot<C, S>
+impl<C, S> Robot<Chat<C>, S>
 where
-    C: ChatService,
+    C: ChatService + 'static,
     S: Store,
 {
     /// Begins constructing a `Robot`.
-    pub fn build(config: Config, chat_service: C, store: S) -> Builder<C, S> {
+    ///
+    /// The chat service is wrapped in a `Chat`, which is what callbacks and other handlers
+    /// receive, so that long messages they send are split to fit the chat service.
+    pub fn build(config: Config, chat_service: C, store: S) -> Builder<Chat<C>, S> {
         Builder {
-            chat_service,
+            chat_service: Chat::new(chat_service),
+            clock: SharedClock::new(),
             config,
+            #[cfg(feature = "http")]
+            endpoints: Vec::new(),
+            event_handlers: Vec::new(),
+            fallbacks: Vec::new(),
+            jobs: Vec::new(),
+            middleware: Vec::new(),
+            reaction_routes: Vec::new(),
             routes: Vec::new(),
+            shutdown_hooks: Vec::new(),
             store,
         }
     }
+}
+
+impl<C, S> Robot<C, S>
+where
+    C: ChatService + 'static,
+    S: Store,
+{
+    /// Returns a handle that can be used to stop the robot once it's running.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            signal: self.shutdown.clone(),
+        }
+    }
 
     /// Starts the robot, connecting to the chat service and listening for incoming messages.
+    ///
+    /// Each message, each event from `ChatService::events`, each scheduled task, and each HTTP
+    /// request is handled concurrently with any others that are still in flight. Messages that a
+    /// callback is waiting for with `IncomingMessage::next_message` are passed to that callback
+    /// instead of the routes.
+    ///
+    /// The robot runs until the stream of incoming messages ends, a message or event produces an
+    /// error, or shutdown is requested with a `ShutdownHandle`. It then shuts down gracefully:
+    ///
+    /// 1. It stops reading incoming messages.
+    /// 1. It signals in-flight callbacks to cancel via `IncomingMessage::cancelled`, failing any
+    ///    waits in `IncomingMessage::next_message`, and waits for them to finish, for at most
+    ///    `Config::shutdown_timeout` if set.
+    /// 1. It runs the hooks added with `Builder::on_shutdown`.
+    /// 1. It calls `ChatService::shutdown` and `Store::flush`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error produced while handling a message or shutting down, including
+    /// timeout errors for callbacks that exceed `Config::timeout` or `Route::with_timeout`, or that
+    /// are still running when `Config::shutdown_timeout` elapses.
     pub async fn run(self) -> Result<(), Error> {
-        let mut incoming_messages = self.chat_service.incoming(self.config.alias);
+        let mut events = self.chat_service.events().fuse();
+        let mut incoming_messages = self.chat_service.incoming(self.config.alias.clone()).fuse();
+        let mut in_flight: FuturesUnordered<_> = self
+            .jobs
+            .iter()
+            .map(|job| self.run_job(job).boxed_local())
+            .collect();
 
-        while let Some(Ok(message)) = StreamExt::next(&mut incoming_messages).await {
-            for route in &self.routes {
-                route.call(self.chat_service.clone(), &message, self.store.clone()).await?
+        #[cfg(feature = "http")]
+        {
+            if let Some(address) = self.config.http_address {
+                if !self.endpoints.is_empty() {
+                    let server = http::serve(
+                        address,
+                        &self.endpoints,
+                        self.chat_service.clone(),
+                        self.store.clone(),
+                        self.cancellation.cancelled(),
+                    );
+
+                    in_flight.push(server.boxed_local());
+                }
+            }
+        }
+
+        let mut shutdown = self.shutdown.cancelled().fuse();
+
+        let mut result = loop {
+            select_biased! {
+                _ = shutdown => break Ok(()),
+                result = in_flight.select_next_some() => {
+                    let result: Result<(), Error> = result;
+
+                    if result.is_err() {
+                        break result;
+                    }
+                }
+                event = events.next() => {
+                    if let Some(Ok(event)) = event {
+                        in_flight.push(self.handle_event(event).boxed_local());
+                    }
+                }
+                message = incoming_messages.next() => match message {
+                    Some(Ok(mut message)) => {
+                        message.set_cancellation(self.cancellation.clone());
+                        message.set_conversations(self.conversations.clone());
+
+                        if let Some(message) = self.conversations.deliver(message) {
+                            in_flight.push(self.dispatch(message).boxed_local());
+                        }
+                    }
+                    _ => break Ok(()),
+                },
+            }
+        };
+
+        drop(events);
+        drop(incoming_messages);
+        self.cancellation.cancel();
+
+        let drain = Box::pin(async {
+            let mut result = Ok(());
+
+            while let Some(next) = in_flight.next().await {
+                result = result.and(next);
+            }
+
+            result
+        });
+
+        result = result.and(match self.config.shutdown_timeout {
+            Some(duration) => match select(drain, Delay::new(duration)).await {
+                Either::Left((drained, _)) => drained,
+                Either::Right(_) => Err(Error::timeout(duration)),
+            },
+            None => drain.await,
+        });
+
+        for hook in &self.shutdown_hooks {
+            let hook_result = hook
+                .call(self.chat_service.clone(), self.store.clone())
+                .await;
+
+            result = result.and(hook_result);
+        }
+
+        let adapter_result = self.chat_service.shutdown().await;
+        let store_result = self
+            .store
+            .flush()
+            .await
+            .map_err(|error| Error::custom(error.to_string()));
+
+        result.and(adapter_result).and(store_result)
+    }
+
+    /// Runs a scheduled task each time its schedule comes due, until the robot shuts down.
+    async fn run_job(&self, job: &Job<C, S>) -> Result<(), Error> {
+        let mut cancelled = self.cancellation.cancelled();
+        let mut last = self.clock.now();
+
+        loop {
+            let now = self.clock.now();
+            let next = match job.schedule.next_after(last) {
+                // Skip runs that were missed while the task was still running.
+                Some(next) if next < now => job.schedule.next_after(now),
+                next => next,
+            };
+
+            let next = match next {
+                Some(next) => next,
+                None => return Ok(()),
+            };
+
+            let wait = (next - now).to_std().unwrap_or_default();
+
+            if let Either::Right(_) = select(self.clock.sleep(wait), &mut cancelled).await {
+                return Ok(());
+            }
+
+            job.task
+                .call(self.chat_service.clone(), self.store.clone())
+                .await?;
+
+            last = next;
+        }
+    }
+
+    /// Invokes each event handler in the order they were added, followed by the reaction
+    /// handlers for the emoji if the event is an added reaction.
+    async fn handle_event(&self, event: Event) -> Result<(), Error> {
+        for handler in &self.event_handlers {
+            handler
+                .call(self.chat_service.clone(), &event, self.store.clone())
+                .await?;
+        }
+
+        if let Event::ReactionAdded(ref reaction) = event {
+            for route in &self.reaction_routes {
+                if route.emoji == reaction.emoji() {
+                    route
+                        .handler
+                        .call(self.chat_service.clone(), reaction, self.store.clone())
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Invokes each route matching the message in order of priority, stopping early if a callback
+    /// stops the message's propagation. If no routes match, the fallback routes are tried instead.
+    async fn dispatch(&self, message: IncomingMessage) -> Result<(), Error> {
+        let mut routes: Vec<&Arc<Route<C, S>>> = self
+            .routes
+            .iter()
+            .filter(|route| route.matches(&message))
+            .collect();
+
+        if routes.is_empty() {
+            routes = self
+                .fallbacks
+                .iter()
+                .filter(|route| route.matches(&message))
+                .collect();
+        }
+
+        for route in routes {
+            let next = Next::new(self.middleware.clone(), route.clone());
+
+            next.run(self.chat_service.clone(), &message, self.store.clone())
+                .await?;
+
+            if message.is_propagation_stopped() {
+                break;
             }
         }
 
         Ok(())
     }
 }
+
+/// A handle for stopping a running `Robot` from another task or thread.
+///
+/// Created by `Robot::shutdown_handle`.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    signal: Cancellation,
+}
+
+impl ShutdownHandle {
+    /// Requests that the robot shut down gracefully.
+    ///
+    /// `Robot::run` returns once shutdown is complete. Calling this more than once, or before the
+    /// robot is running, has no additional effect.
+    pub fn shutdown(&self) {
+        self.signal.cancel();
+    }
+}
+
+/// Sorts routes in order of descending priority, keeping routes of equal priority in order.
+fn prioritize<C, S>(mut routes: Vec<Route<C, S>>) -> Vec<Arc<Route<C, S>>>
+where
+    C: ChatService,
+{
+    routes.sort_by_key(|route| Reverse(route.priority()));
+
+    routes.into_iter().map(Arc::new).collect()
+}
@@ -29,6 +29,6 @@ impl Room {
 
     /// A human-readable name for the room.
     pub fn name(&self) -> Option<&str> {
-        self.name.as_ref().map(String::as_str)
+        self.name.as_deref()
     }
 }
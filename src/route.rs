@@ -1,17 +1,29 @@
 //! Routes match incoming messages to callbacks.
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use regex::Regex;
+use regex::{escape, Regex};
 
 use crate::{
     callback::{Callback, CallbackFuture},
-    chat_service::ChatService,
+    chat_service::{ChatService, PresenceChatService},
+    command::{Command, FromArgs},
     message::IncomingMessage,
     result::Error,
     store::Store,
 };
 
+pub use self::confirmation::Confirmation;
+pub use self::guard::Guard;
+pub use self::rate_limit::RateLimit;
+pub(crate) use self::typing::Typing;
+
+mod confirmation;
+mod guard;
+mod rate_limit;
+mod typing;
+
 /// A route determines whether or not to invoke a callback by matching incoming messages against a
 /// set of criteria.
 pub struct Route<C, S>
@@ -19,9 +31,18 @@ where
     C: ChatService,
 {
     callback: Box<dyn Callback<C, S>>,
+    confirmation: Option<Confirmation>,
     eavesdrop: bool,
+    examples: Vec<String>,
+    group: Option<String>,
+    guards: Vec<Guard>,
+    help: Option<String>,
     namespace: &'static str,
     pattern: Regex,
+    priority: i32,
+    rate_limit: Option<RateLimit>,
+    timeout: Option<Duration>,
+    typing: Option<Typing<C>>,
 }
 
 impl<C, S> Route<C, S>
@@ -46,12 +67,66 @@ where
 
         Ok(Route {
             callback: Box::new(callback),
+            confirmation: None,
             eavesdrop,
+            examples: Vec::new(),
+            group: None,
+            guards: Vec::new(),
+            help: None,
             namespace,
             pattern: regex,
+            priority: 0,
+            rate_limit: None,
+            timeout: None,
+            typing: None,
         })
     }
 
+    /// Constructs a new `Route` for a command-style message such as `deploy app --env prod`.
+    ///
+    /// The route matches directed messages that begin with the given name, which is also used as
+    /// the route's namespace. The rest of the message is parsed into `A`, which is passed to the
+    /// callback. If the arguments are invalid, or if they include `--help`, the robot replies with
+    /// the command's usage instead of invoking the callback. The usage is also added as an example
+    /// for the `help` command.
+    pub fn command<A, F>(name: &'static str, callback: F) -> Self
+    where
+        A: FromArgs + 'static,
+        F: Fn(Arc<C>, &IncomingMessage, A, S) -> CallbackFuture + 'static,
+        S: Store,
+    {
+        let command = Command::<A, F>::new(name, callback);
+        let usage = command.spec().usage();
+        let pattern = format!(r"(?i)\A\s*{}(?:\s|\z)", escape(name));
+
+        Route::new(&pattern, false, name, command)
+            .expect("creating command regex")
+            .with_example(usage)
+    }
+
+    /// Sets a human-readable description of what the route does.
+    ///
+    /// The description is shown by the built-in `help` command and is searched when the command is
+    /// given a search term.
+    pub fn with_help<H>(mut self, help: H) -> Self
+    where
+        H: Into<String>,
+    {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Adds an example of a message that triggers the route, such as `deploy <app>`.
+    ///
+    /// Examples are shown by the built-in `help` command in place of the route's pattern.
+    pub fn with_example<E>(mut self, example: E) -> Self
+    where
+        E: Into<String>,
+    {
+        self.examples.push(example.into());
+        self
+    }
+
     /// Whether or not the robot should "eavesdrop" to look for this message.
     ///
     /// When `true`, the message does not need to be directed to the robot by name or alias.
@@ -59,15 +134,142 @@ where
         self.eavesdrop
     }
 
+    /// Adds a guard that incoming messages must satisfy for the route to be invoked.
+    ///
+    /// When multiple guards are added, all of them must pass.
+    pub fn with_guard<G>(mut self, guard: G) -> Self
+    where
+        G: Into<Guard>,
+    {
+        self.guards.push(guard.into());
+        self
+    }
+
+    /// Restricts the route to members of the given authorization group.
+    ///
+    /// When a user outside the group sends a matching message, the robot replies with a denial
+    /// instead of invoking the callback. The route is also hidden from the user by the `help`
+    /// command.
+    pub fn with_required_group<G>(mut self, group: G) -> Self
+    where
+        G: Into<String>,
+    {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Sets the route's priority, which defaults to zero.
+    ///
+    /// Routes with a higher priority are tried before routes with a lower priority. Routes with
+    /// the same priority are tried in the order they were added. Combined with
+    /// `IncomingMessage::stop_propagation`, this allows a specific route to prevent a more general
+    /// one from running.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Limits how often the route can be invoked.
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Requires the user to confirm each invocation of the route before the callback runs.
+    pub fn with_confirmation(mut self, confirmation: Confirmation) -> Self {
+        self.confirmation = Some(confirmation);
+        self
+    }
+
+    /// Sets the maximum amount of time the route's callback may run, overriding `Config::timeout`.
+    ///
+    /// A callback that runs longer than this is dropped and fails with a timeout error, which
+    /// passes back through any middleware.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Examples of messages that trigger the route.
+    pub fn examples(&self) -> &[String] {
+        &self.examples
+    }
+
+    /// A human-readable description of what the route does, if any.
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    /// Whether or not the route should be invoked for the given message.
+    ///
+    /// Unless the route eavesdrops, the message must be directed to the robot. The message must
+    /// also satisfy all of the route's guards.
+    pub fn matches(&self, message: &IncomingMessage) -> bool {
+        (self.eavesdrop || message.is_directed())
+            && self.pattern.is_match(message.body())
+            && self.guards.iter().all(|guard| guard.check(message))
+    }
+
     /// The namespace to use for any data persisted within the callback.
     pub fn namespace(&self) -> &'static str {
         self.namespace
     }
 
+    /// The limit on how often the route can be invoked, if any.
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    /// The confirmation required before the route's callback runs, if any.
+    pub fn confirmation(&self) -> Option<&Confirmation> {
+        self.confirmation.as_ref()
+    }
+
+    /// The maximum amount of time the route's callback may run, if set for the route.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Whether or not the robot shows that it's typing while the route's callback runs.
+    pub fn shows_typing(&self) -> bool {
+        self.typing.is_some()
+    }
+
+    /// The operations for showing a typing indicator, if the route shows one.
+    pub(crate) fn typing(&self) -> Option<&Typing<C>> {
+        self.typing.as_ref()
+    }
+
+    /// The authorization group required to invoke the route, if any.
+    pub fn required_group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
     /// The route's regular expression.
     pub fn pattern(&self) -> &Regex {
         &self.pattern
     }
+
+    /// The route's priority.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
+impl<C, S> Route<C, S>
+where
+    C: PresenceChatService,
+{
+    /// Shows that the robot is typing where the message came from while the route's callback
+    /// runs, for callbacks that take a while to reply.
+    ///
+    /// The indicator is started once any confirmation has been given, and stopped when the
+    /// callback finishes or times out. Errors from the chat service showing or hiding it are
+    /// ignored, so they never stop the route.
+    pub fn with_typing(mut self) -> Self {
+        self.typing = Some(Typing::new());
+        self
+    }
 }
 
 impl<C, S> Callback<C, S> for Route<C, S>
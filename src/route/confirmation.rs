@@ -0,0 +1,117 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{chat_service::ChatService, message::IncomingMessage, result::Error};
+
+/// The characters used in confirmation tokens, leaving out those that are easily confused.
+const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const TOKEN_LENGTH: usize = 6;
+
+/// A requirement that the user confirm they meant to invoke a route before its callback runs,
+/// useful for destructive commands.
+///
+/// When a message matches the route, the robot asks the user to reply with `yes`, or with a random
+/// token if `token` is set, within the given amount of time. The callback only runs if the user's
+/// next message in the same room and thread is that reply. Anything else, or no reply in time,
+/// cancels the invocation.
+#[derive(Clone, Debug)]
+pub struct Confirmation {
+    timeout: Duration,
+    token: bool,
+}
+
+impl Confirmation {
+    /// Creates a new `Confirmation` requiring the user to reply `yes` within `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Confirmation {
+            timeout,
+            token: false,
+        }
+    }
+
+    /// Requires the user to reply with a random token instead of `yes`, so a confirmation can't be
+    /// given out of habit.
+    pub fn token(mut self) -> Self {
+        self.token = true;
+        self
+    }
+
+    /// Asks the user to confirm the message, returning whether or not they did.
+    pub(crate) async fn confirm<C>(
+        &self,
+        chat: &C,
+        message: &IncomingMessage,
+    ) -> Result<bool, Error>
+    where
+        C: ChatService,
+    {
+        let expected = if self.token {
+            random_token()
+        } else {
+            "yes".to_owned()
+        };
+
+        let prompt = format!(
+            "Are you sure? Reply \"{}\" within {} to continue.",
+            expected,
+            describe(self.timeout)
+        );
+
+        chat.send_message(message.reply_in_thread(prompt)).await?;
+
+        let reply = match message.next_message(self.timeout).await {
+            Ok(answer) if answer.body().trim().eq_ignore_ascii_case(&expected) => return Ok(true),
+            Ok(_) => "OK, cancelled.".to_owned(),
+            Err(error) if error.is_timeout() => format!(
+                "Cancelled, since there was no reply within {}.",
+                describe(self.timeout)
+            ),
+            // The robot is shutting down, so there's no one to tell.
+            Err(_) if message.is_cancelled() => return Ok(false),
+            Err(error) => return Err(error),
+        };
+
+        chat.send_message(message.reply_in_thread(reply)).await?;
+
+        Ok(false)
+    }
+}
+
+/// Describes a duration in seconds or, if it isn't a whole number of seconds, milliseconds.
+fn describe(duration: Duration) -> String {
+    let (amount, unit) = if duration.subsec_millis() == 0 {
+        (duration.as_secs(), "second")
+    } else {
+        (duration.as_millis() as u64, "millisecond")
+    };
+
+    if amount == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", amount, unit)
+    }
+}
+
+/// Generates a short random token for the user to type back.
+fn random_token() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0);
+
+    hasher.write_u128(nanos);
+
+    let mut value = hasher.finish();
+    let base = ALPHABET.len() as u64;
+
+    (0..TOKEN_LENGTH)
+        .map(|_| {
+            let character = ALPHABET[(value % base) as usize] as char;
+            value /= base;
+
+            character
+        })
+        .collect()
+}
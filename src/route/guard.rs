@@ -0,0 +1,111 @@
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::ops::Not;
+use std::sync::Arc;
+
+use crate::message::IncomingMessage;
+
+/// A predicate that an incoming message must satisfy for a route to be invoked.
+///
+/// Guards are checked by the robot after the route's pattern has matched and before its callback
+/// runs. They can be combined with `and`, `or`, and `!`.
+#[derive(Clone)]
+pub struct Guard {
+    predicate: Arc<dyn Fn(&IncomingMessage) -> bool>,
+}
+
+impl Guard {
+    /// Creates a `Guard` from an arbitrary predicate.
+    pub fn new<F>(predicate: F) -> Self
+    where
+        F: Fn(&IncomingMessage) -> bool + 'static,
+    {
+        Guard {
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    /// A guard that passes for messages sent in the room with the given ID.
+    pub fn room<I>(id: I) -> Self
+    where
+        I: Into<String>,
+    {
+        Guard::rooms(vec![id])
+    }
+
+    /// A guard that passes for messages sent in any of the rooms with the given IDs.
+    pub fn rooms<I, R>(ids: I) -> Self
+    where
+        I: IntoIterator<Item = R>,
+        R: Into<String>,
+    {
+        let ids: Vec<String> = ids.into_iter().map(Into::into).collect();
+
+        Guard::new(move |message| {
+            message
+                .room()
+                .is_some_and(|room| ids.iter().any(|id| id == room.id()))
+        })
+    }
+
+    /// A guard that passes for messages sent by the user with the given ID.
+    pub fn user<I>(id: I) -> Self
+    where
+        I: Into<String>,
+    {
+        Guard::users(vec![id])
+    }
+
+    /// A guard that passes for messages sent by any of the users with the given IDs.
+    pub fn users<I, U>(ids: I) -> Self
+    where
+        I: IntoIterator<Item = U>,
+        U: Into<String>,
+    {
+        let ids: Vec<String> = ids.into_iter().map(Into::into).collect();
+
+        Guard::new(move |message| ids.iter().any(|id| id == message.user().id()))
+    }
+
+    /// A guard that passes for direct messages, i.e. those not sent in a room.
+    pub fn direct_message() -> Self {
+        Guard::new(|message| message.room().is_none())
+    }
+
+    /// Combines two guards into one that passes only if both pass.
+    pub fn and(self, other: Guard) -> Self {
+        Guard::new(move |message| self.check(message) && other.check(message))
+    }
+
+    /// Combines two guards into one that passes if either passes.
+    pub fn or(self, other: Guard) -> Self {
+        Guard::new(move |message| self.check(message) || other.check(message))
+    }
+
+    /// Whether or not the message satisfies the guard.
+    pub fn check(&self, message: &IncomingMessage) -> bool {
+        (self.predicate)(message)
+    }
+}
+
+impl Not for Guard {
+    type Output = Guard;
+
+    fn not(self) -> Self::Output {
+        Guard::new(move |message| !self.check(message))
+    }
+}
+
+impl Debug for Guard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Guard").finish()
+    }
+}
+
+impl<F> From<F> for Guard
+where
+    F: Fn(&IncomingMessage) -> bool + 'static,
+{
+    fn from(predicate: F) -> Self {
+        Guard::new(predicate)
+    }
+}
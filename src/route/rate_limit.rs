@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{message::IncomingMessage, result::Error, schedule::Clock, store::Store};
+
+/// A limit on how often a route can be invoked, implemented as a token bucket.
+///
+/// A bucket holds up to `capacity` tokens and refills at a rate of `capacity` tokens per `period`.
+/// Each invocation of the route takes one token, and messages that arrive when the bucket is empty
+/// are ignored, optionally with a reply. By default, a single bucket is shared by everyone using
+/// the route. Use `per_user` and `per_room` to give each user and/or room its own bucket.
+///
+/// Buckets are persisted in the robot's `Store`, so limits survive restarts. Time is measured with
+/// the clock set with `Builder::clock`.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    capacity: u32,
+    /// Held while taking a token, so concurrent messages can't all take the same one.
+    lock: Arc<Mutex<()>>,
+    per_room: bool,
+    per_user: bool,
+    period: Duration,
+    reply: Option<String>,
+}
+
+impl RateLimit {
+    /// Creates a new `RateLimit` allowing `capacity` invocations per `period`.
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        RateLimit {
+            capacity,
+            lock: Arc::new(Mutex::new(())),
+            per_room: false,
+            per_user: false,
+            period,
+            reply: None,
+        }
+    }
+
+    /// Gives each user their own bucket.
+    pub fn per_user(mut self) -> Self {
+        self.per_user = true;
+        self
+    }
+
+    /// Gives each room its own bucket. Direct messages share a single bucket.
+    pub fn per_room(mut self) -> Self {
+        self.per_room = true;
+        self
+    }
+
+    /// Sets a reply to send when a message is ignored because the limit has been reached.
+    pub fn reply<R>(mut self, reply: R) -> Self
+    where
+        R: Into<String>,
+    {
+        self.reply = Some(reply.into());
+        self
+    }
+
+    /// The reply to send when the limit has been reached, if any.
+    pub fn reply_text(&self) -> Option<&str> {
+        self.reply.as_deref()
+    }
+
+    /// Takes a token from the bucket for the message, returning `false` if the bucket is empty.
+    pub(crate) async fn acquire<S>(
+        &self,
+        store: &S,
+        clock: &dyn Clock,
+        namespace: &str,
+        message: &IncomingMessage,
+    ) -> Result<bool, Error>
+    where
+        S: Store,
+    {
+        let store = store.scoped("rate_limit").scoped(namespace);
+        let key = self.key(message);
+        let _guard = self.lock.lock().await;
+        let now = clock.now().timestamp_millis().max(0) as u64;
+
+        let mut bucket = store
+            .get_json(&key)
+            .await?
+            .unwrap_or_else(|| Bucket::full(self.capacity, now));
+        let acquired = bucket.take(self, now);
+
+        store.set_json(&key, &bucket).await?;
+
+        Ok(acquired)
+    }
+
+    fn key(&self, message: &IncomingMessage) -> String {
+        let mut parts = Vec::new();
+
+        if self.per_user {
+            parts.push(format!("user:{}", message.user().id()));
+        }
+
+        if self.per_room {
+            match message.room() {
+                Some(room) => parts.push(format!("room:{}", room.id())),
+                None => parts.push("direct".to_owned()),
+            }
+        }
+
+        if parts.is_empty() {
+            "all".to_owned()
+        } else {
+            parts.join(":")
+        }
+    }
+}
+
+/// The persisted state of a token bucket.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Bucket {
+    tokens: f64,
+    updated: u64,
+}
+
+impl Bucket {
+    fn full(capacity: u32, now: u64) -> Self {
+        Bucket {
+            tokens: f64::from(capacity),
+            updated: now,
+        }
+    }
+
+    /// Refills the bucket for the time elapsed since it was last updated and takes a token.
+    fn take(&mut self, limit: &RateLimit, now: u64) -> bool {
+        let elapsed = now.saturating_sub(self.updated) as f64;
+        let rate = f64::from(limit.capacity) / limit.period.as_millis().max(1) as f64;
+
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(limit.capacity));
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Bucket, RateLimit};
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let limit = RateLimit::new(2, Duration::from_secs(10));
+        let mut bucket = Bucket {
+            tokens: 2.0,
+            updated: 0,
+        };
+
+        assert!(bucket.take(&limit, 0));
+        assert!(bucket.take(&limit, 0));
+        assert!(!bucket.take(&limit, 4_000));
+        assert!(bucket.take(&limit, 5_000));
+        assert!(bucket.take(&limit, 60_000));
+        assert!(bucket.take(&limit, 60_000));
+        assert!(!bucket.take(&limit, 60_000));
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{
+    chat_service::PresenceChatService,
+    message::Target,
+    result::{Error, Success},
+};
+
+/// The operations for showing a typing indicator while a route's callback runs.
+///
+/// These are captured when the option is set on the route, so the robot can use them without
+/// requiring every chat service to support typing indicators.
+pub(crate) struct Typing<C> {
+    start: fn(&C, &Target) -> Success,
+    stop: fn(&C, &Target) -> Success,
+}
+
+impl<C> Typing<C>
+where
+    C: PresenceChatService,
+{
+    pub(crate) fn new() -> Self {
+        Typing {
+            start: C::start_typing,
+            stop: C::stop_typing,
+        }
+    }
+}
+
+impl<C> Clone for Typing<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for Typing<C> {}
+
+impl<C> Typing<C> {
+    /// Shows that the robot is typing to the target.
+    pub(crate) async fn start(&self, chat: &C, target: &Target) -> Result<(), Error> {
+        (self.start)(chat, target).await
+    }
+
+    /// Stops showing that the robot is typing to the target.
+    pub(crate) async fn stop(&self, chat: &C, target: &Target) -> Result<(), Error> {
+        (self.stop)(chat, target).await
+    }
+}
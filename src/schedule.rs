@@ -0,0 +1,93 @@
+//! Types for running tasks at regular intervals or at specific times.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::{callback::Task, result::Error};
+
+pub use self::clock::{Clock, ManualClock, SharedClock, SystemClock};
+
+mod clock;
+mod cron;
+
+/// When a scheduled task should run.
+///
+/// Tasks are scheduled with `Builder::schedule`. Cron expressions are evaluated in UTC.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rustin::schedule::Schedule;
+///
+/// // Every fifteen minutes after the robot starts.
+/// let interval = Schedule::every(Duration::from_secs(15 * 60));
+///
+/// // At 9:30 every weekday.
+/// let standup = Schedule::cron("30 9 * * 1-5").unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    kind: Kind,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Cron(cron::Cron),
+    Interval(Duration),
+}
+
+impl Schedule {
+    /// Creates a schedule that runs repeatedly with `period` between each run.
+    ///
+    /// The first run happens one `period` after the robot starts.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn every(period: Duration) -> Self {
+        assert!(
+            period > Duration::from_secs(0),
+            "schedule period must not be zero"
+        );
+
+        Schedule {
+            kind: Kind::Interval(period),
+        }
+    }
+
+    /// Creates a schedule from a cron expression.
+    ///
+    /// The expression has five fields separated by whitespace: minute (0-59), hour (0-23), day of
+    /// the month (1-31), month (1-12), and day of the week (0-7, where both 0 and 7 are Sunday).
+    /// Each field is either `*` or a comma-separated list of values and ranges like `1-5`, and may
+    /// have a step like `*/15`. As in standard cron, when both the day of the month and the day of
+    /// the week are restricted, a day matching either of them matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the expression is invalid.
+    pub fn cron(expression: &str) -> Result<Self, Error> {
+        Ok(Schedule {
+            kind: Kind::Cron(expression.parse()?),
+        })
+    }
+
+    /// The next time the schedule runs after `time`, if any.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.kind {
+            Kind::Cron(ref cron) => cron.next_after(time),
+            Kind::Interval(period) => chrono::Duration::from_std(period)
+                .ok()
+                .and_then(|period| time.checked_add_signed(period)),
+        }
+    }
+}
+
+/// A task paired with the schedule it runs on.
+pub(crate) struct Job<C, S> {
+    pub(crate) schedule: Schedule,
+    pub(crate) task: Box<dyn Task<C, S>>,
+}
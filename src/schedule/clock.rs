@@ -0,0 +1,148 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use futures_timer::Delay;
+
+/// A source of the current time for scheduled tasks.
+///
+/// The robot uses `SystemClock` unless another clock is set with `Builder::clock`. Tests can use
+/// `ManualClock` to control the passage of time.
+pub trait Clock {
+    /// The current time.
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Returns a future that completes once `duration` has passed.
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>>;
+}
+
+/// A clock that follows the system's time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        DateTime::from(SystemTime::now())
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(Delay::new(duration))
+    }
+}
+
+/// The robot's clock, which follows whichever clock was most recently set with `Builder::clock`.
+///
+/// All clones of a `SharedClock` follow the same clock. Plugins get one from
+/// `Builder::shared_clock`, so they use the robot's clock even if it is set after they are added.
+#[derive(Clone)]
+pub struct SharedClock {
+    inner: Rc<RefCell<Box<dyn Clock>>>,
+}
+
+impl SharedClock {
+    /// Creates a new `SharedClock` following `SystemClock`.
+    pub(crate) fn new() -> Self {
+        SharedClock {
+            inner: Rc::new(RefCell::new(Box::new(SystemClock))),
+        }
+    }
+
+    /// Makes all clones of the clock follow `clock` instead.
+    pub(crate) fn set<K>(&self, clock: K)
+    where
+        K: Clock + 'static,
+    {
+        *self.inner.borrow_mut() = Box::new(clock);
+    }
+}
+
+impl Clock for SharedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.inner.borrow().now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+        self.inner.borrow().sleep(duration)
+    }
+}
+
+/// A clock whose time only changes when it is told to.
+///
+/// All clones of a `ManualClock` share the same time. Sleeping tasks wake up once the clock has
+/// been advanced past the end of their sleep.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    now: DateTime<Utc>,
+    wakers: Vec<Waker>,
+}
+
+impl ManualClock {
+    /// Creates a new `ManualClock` set to `now`.
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock {
+            inner: Arc::new(Mutex::new(Inner {
+                now,
+                wakers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let duration = chrono::Duration::from_std(duration).expect("advancing manual clock");
+        let mut inner = self.inner.lock().expect("accessing manual clock");
+
+        inner.now += duration;
+
+        for waker in inner.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.inner.lock().expect("accessing manual clock").now
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+        let duration = chrono::Duration::from_std(duration).expect("sleeping on manual clock");
+
+        Box::pin(Sleep {
+            clock: self.clone(),
+            deadline: self.now() + duration,
+        })
+    }
+}
+
+/// A future that completes once a `ManualClock` reaches its deadline.
+struct Sleep {
+    clock: ManualClock,
+    deadline: DateTime<Utc>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.clock.inner.lock().expect("accessing manual clock");
+
+        if inner.now >= self.deadline {
+            Poll::Ready(())
+        } else {
+            inner.wakers.push(cx.waker().clone());
+
+            Poll::Pending
+        }
+    }
+}
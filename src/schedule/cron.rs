@@ -0,0 +1,213 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+
+use crate::result::Error;
+
+/// How many years ahead to look for a matching time before giving up, which only happens for
+/// expressions that can never match, like `0 0 30 2 *`.
+const SEARCH_YEARS: i32 = 5;
+
+/// A parsed cron expression, each field stored as a bit set of the values it matches.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    /// The first minute after `time` matching the expression, if any.
+    pub(super) fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let time = time.naive_utc();
+        let limit = time.year() + SEARCH_YEARS;
+        let mut candidate = time.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+
+        while candidate.year() <= limit {
+            let date = candidate.date();
+
+            if !has(self.months, date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+
+                candidate = midnight(NaiveDate::from_ymd_opt(year, month, 1)?);
+            } else if !self.matches_day(date) {
+                candidate = midnight(date.succ_opt()?);
+            } else if !has(self.hours, candidate.hour()) {
+                candidate = candidate.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, candidate.minute()) {
+                candidate += Duration::minutes(1);
+            } else {
+                return Some(Utc.from_utc_datetime(&candidate));
+            }
+        }
+
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+
+        if self.days_restricted && self.weekdays_restricted {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+}
+
+impl FromStr for Cron {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| {
+            Error::custom(format!(
+                "invalid cron expression \"{}\": {}",
+                expression, reason
+            ))
+        };
+
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+
+        if fields.len() != 5 {
+            return Err(invalid(format!(
+                "expected 5 fields, found {}",
+                fields.len()
+            )));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7).map_err(invalid)?;
+
+        // Both 0 and 7 mean Sunday.
+        if has(weekdays, 7) {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(Cron {
+            minutes: parse_field(fields[0], 0, 59).map_err(invalid)?,
+            hours: parse_field(fields[1], 0, 23).map_err(invalid)?,
+            days: parse_field(fields[2], 1, 31).map_err(invalid)?,
+            months: parse_field(fields[3], 1, 12).map_err(invalid)?,
+            weekdays,
+            days_restricted: !fields[2].starts_with('*'),
+            weekdays_restricted: !fields[4].starts_with('*'),
+        })
+    }
+}
+
+/// Parses one field of a cron expression into a bit set of the values it matches.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut set = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(index) => (&part[..index], Some(parse_value(&part[index + 1..])?)),
+            None => (part, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some(index) = range.find('-') {
+            (
+                parse_value(&range[..index])?,
+                parse_value(&range[index + 1..])?,
+            )
+        } else {
+            let value = parse_value(range)?;
+
+            (value, if step.is_some() { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!("\"{}\" is outside the range {}-{}", part, min, max));
+        }
+
+        let step = match step {
+            Some(0) => return Err(format!("\"{}\" has a step of zero", part)),
+            Some(step) => step as usize,
+            None => 1,
+        };
+
+        for value in (start..=end).step_by(step) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+fn parse_value(value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a number", value))
+}
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).expect("creating midnight")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::Cron;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn next(expression: &str, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        expression.parse::<Cron>().unwrap().next_after(time)
+    }
+
+    #[test]
+    fn finds_the_next_matching_minute() {
+        // 2020-01-01 was a Wednesday.
+        let now = at(2020, 1, 1, 9, 30);
+
+        assert_eq!(next("* * * * *", now), Some(at(2020, 1, 1, 9, 31)));
+        assert_eq!(next("*/15 * * * *", now), Some(at(2020, 1, 1, 9, 45)));
+        assert_eq!(next("30 9 * * *", now), Some(at(2020, 1, 2, 9, 30)));
+        assert_eq!(
+            next("0 9 * * 1-5", at(2020, 1, 3, 10, 0)),
+            Some(at(2020, 1, 6, 9, 0))
+        );
+        assert_eq!(next("0 0 1 */3 *", now), Some(at(2020, 4, 1, 0, 0)));
+        assert_eq!(next("0 12 * * 7", now), Some(at(2020, 1, 5, 12, 0)));
+        assert_eq!(next("0 0 29 2 *", now), Some(at(2020, 2, 29, 0, 0)));
+        assert_eq!(next("0 0 30 2 *", now), None);
+    }
+
+    #[test]
+    fn matches_either_restricted_day_field() {
+        let now = at(2020, 1, 1, 0, 0);
+
+        assert_eq!(next("0 0 15 * 5", now), Some(at(2020, 1, 3, 0, 0)));
+        assert_eq!(next("0 0 2 * 5", now), Some(at(2020, 1, 2, 0, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in &[
+            "* * * *",
+            "60 * * * *",
+            "* 5-1 * * *",
+            "*/0 * * * *",
+            "a * * * *",
+        ] {
+            assert!(expression.parse::<Cron>().is_err(), "{}", expression);
+        }
+    }
+}
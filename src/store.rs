@@ -8,6 +8,7 @@ use std::pin::Pin;
 use std::sync::{Arc, RwLock};
 
 use futures::future::{err, ok};
+use serde::{de::DeserializeOwned, Serialize};
 
 use crate::result::Error;
 
@@ -17,11 +18,11 @@ pub trait Store: Clone + Send + Sync + 'static {
     type Error: StdError + Send + Sync + 'static;
 
     /// Gets the value of the given key, if any.
-    fn get<K>(&self, key: K) -> Pin<Box<dyn Future<Output = Result<Option<String>, Self::Error>>>>
+    fn get<K>(&self, key: K) -> StoreFuture<Option<String>, Self::Error>
     where
         K: AsRef<str> + Display;
     /// Sets the given key to the given value.
-    fn set<K, V>(&self, key: K, value: V) -> Pin<Box<dyn Future<Output = Result<(), Self::Error>>>>
+    fn set<K, V>(&self, key: K, value: V) -> StoreFuture<(), Self::Error>
     where
         K: Display + Into<String>,
         V: Into<String>;
@@ -34,10 +35,19 @@ pub trait Store: Clone + Send + Sync + 'static {
     fn separator(&self) -> &'static str {
         ":"
     }
+    /// Writes any buffered data to the underlying data store.
+    ///
+    /// Called by the robot when it shuts down. The default implementation does nothing.
+    fn flush(&self) -> StoreFuture<(), Self::Error> {
+        Box::pin(ok(()))
+    }
 }
 
+/// The type returned by `Store` operations.
+pub type StoreFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>>>>;
+
 /// A `Store` that lives in program memory, emptying when the program exits.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Memory {
     data: Arc<RwLock<HashMap<String, String>>>,
 }
@@ -54,19 +64,19 @@ impl Memory {
 impl Store for Memory {
     type Error = Error;
 
-    fn get<K>(&self, key: K) -> Pin<Box<dyn Future<Output = Result<Option<String>, Self::Error>>>>
+    fn get<K>(&self, key: K) -> StoreFuture<Option<String>, Self::Error>
     where
         K: AsRef<str> + Display,
     {
         let future = match self.data.read() {
-            Ok(data) => ok(data.get(key.as_ref()).map(|value| value.clone())),
+            Ok(data) => ok(data.get(key.as_ref()).cloned()),
             Err(error) => err(Error::custom(error.to_string())),
         };
 
         Box::pin(future)
     }
 
-    fn set<K, V>(&self, key: K, value: V) -> Pin<Box<dyn Future<Output = Result<(), Self::Error>>>>
+    fn set<K, V>(&self, key: K, value: V) -> StoreFuture<(), Self::Error>
     where
         K: Display + Into<String>,
         V: Into<String>,
@@ -118,6 +128,30 @@ where
             prefix: prefix.into(),
         }
     }
+
+    /// Gets the value of the given key deserialized from JSON, if any.
+    pub async fn get_json<T>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        match self.get(key).await {
+            Ok(Some(value)) => Ok(Some(serde_json::from_str(&value)?)),
+            Ok(None) => Ok(None),
+            Err(error) => Err(Error::custom(error.to_string())),
+        }
+    }
+
+    /// Sets the given key to the value serialized as JSON.
+    pub async fn set_json<T>(&self, key: &str, value: &T) -> Result<(), Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let value = serde_json::to_string(value)?;
+
+        self.set(key, value)
+            .await
+            .map_err(|error| Error::custom(error.to_string()))
+    }
 }
 
 impl<S> Store for ScopedStore<S>
@@ -126,7 +160,7 @@ where
 {
     type Error = S::Error;
 
-    fn get<K>(&self, key: K) -> Pin<Box<dyn Future<Output = Result<Option<String>, Self::Error>>>>
+    fn get<K>(&self, key: K) -> StoreFuture<Option<String>, Self::Error>
     where
         K: AsRef<str> + Display,
     {
@@ -135,7 +169,7 @@ where
         self.parent.get(key)
     }
 
-    fn set<K, V>(&self, key: K, value: V) -> Pin<Box<dyn Future<Output = Result<(), Self::Error>>>>
+    fn set<K, V>(&self, key: K, value: V) -> StoreFuture<(), Self::Error>
     where
         K: Display + Into<String>,
         V: Into<String>,
@@ -159,4 +193,8 @@ where
     fn separator(&self) -> &'static str {
         self.parent.separator()
     }
+
+    fn flush(&self) -> StoreFuture<(), Self::Error> {
+        self.parent.flush()
+    }
 }